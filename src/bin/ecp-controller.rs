@@ -1,7 +1,10 @@
 use clap::{App, Arg, ArgMatches};
 use ecp::bluetooth::{BleOptions, BluetoothReceiver};
-use ecp::color::{Color, ColorMap};
-use ecp::controller::{Controller, Renderer};
+use ecp::color::{Calibration, Color, ColorMap};
+use ecp::controller::{Idle, PiController, Renderer, WithPixelFormats};
+use ecp::pixel::PixelFormat;
+use ecp::power::PowerLimit;
+use ecp::safety::FlashLimiter;
 use ecp::Receiver;
 use gpio_cdev::Chip;
 use ham::rfm69::Rfm69;
@@ -10,26 +13,12 @@ use spidev::Spidev;
 use std::num::{NonZeroU16, NonZeroU8};
 use std::str::FromStr;
 use std::thread::Builder;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub fn main() {
     let parser = parser();
     let args = parser.get_matches();
-    let pin = u8::from_str(args.value_of("led_pin").unwrap()).unwrap() as i32;
-    let count = u16::from_str(args.value_of("led_count").unwrap()).unwrap() as i32;
-    let channel = rs_ws281x::ChannelBuilder::new()
-        .pin(pin)
-        .strip_type(rs_ws281x::StripType::Ws2812)
-        .count(count)
-        .brightness(255)
-        .build();
-    let ctl = rs_ws281x::ControllerBuilder::new()
-        .freq(800_000)
-        .channel(0, channel)
-        .build()
-        .unwrap();
-
-    let mode = args.value_of("mode").unwrap();
+    let mode = args.value_of("mode").unwrap().to_string();
     let verbose = args.occurrences_of("verbose") as u8;
     let mut color_map = ColorMap::default();
     color_map[2] = Color::YELLOW;
@@ -44,25 +33,27 @@ pub fn main() {
     } else {
         0
     };
-    match mode {
+    // the LED driver can't be moved between threads, so it is created on the renderer's thread
+    let handle = match mode.as_str() {
         "bluetooth" => {
             #[cfg(feature = "bluetooth")]
             {
-                let options = BleOptions {
-                    verbose,
-                    stats: recvstats,
-                };
-                let recv = BluetoothReceiver::new("/org/bluez/hci0".to_string(), options).unwrap();
-                let mut renderer = Renderer::new(recv, ctl);
-                renderer.color_map = color_map;
-                render(renderer, verbose);
+                Renderer::spawn_with(60.0, move || {
+                    let options = BleOptions {
+                        verbose,
+                        stats: recvstats,
+                    };
+                    let recv =
+                        BluetoothReceiver::new("/org/bluez/hci0".to_string(), options).unwrap();
+                    Ok(renderer(recv, &args, verbose, color_map))
+                })
             }
             #[cfg(not(feature = "bluetooth"))]
             {
                 panic!("bluetooth was not enabled at compile time.")
             }
         }
-        "ham" => {
+        "ham" => Renderer::spawn_with(60.0, move || {
             // let pin = u8::from_str(args.value_of("led_pin").unwrap()).unwrap() as i32;
             // let count = u16::from_str(args.value_of("led_count").unwrap()).unwrap() as i32;
             let mut chip = Chip::new("/dev/gpiochip0").unwrap();
@@ -77,19 +68,80 @@ pub fn main() {
             rfm.set_bitrate(45000).unwrap();
             let mut recv = rfm.into_packet_receiver().unwrap();
             recv.start().unwrap();
-            let renderer = Renderer::new(recv, ctl);
-            render(renderer, verbose);
-        }
+            Ok(renderer(recv, &args, verbose, ColorMap::default()))
+        }),
+        _ => unreachable!(),
+    }
+    .unwrap();
+    panic!("Rendering quit: {:?}", handle.wait());
+}
+fn controller(args: &ArgMatches) -> WithPixelFormats<PiController> {
+    let pin = u8::from_str(args.value_of("led_pin").unwrap()).unwrap() as i32;
+    let count = u16::from_str(args.value_of("led_count").unwrap()).unwrap() as i32;
+    // RGBW strips have a white channel in the fourth byte of each LED
+    let (strip_type, format) = match args.value_of("strip").unwrap() {
+        "ws2812" => (rs_ws281x::StripType::Ws2812, PixelFormat::BGRX),
+        "sk6812w" => (rs_ws281x::StripType::Sk6812W, PixelFormat::BGRW),
         _ => unreachable!(),
     };
+    let channel = rs_ws281x::ChannelBuilder::new()
+        .pin(pin)
+        .strip_type(strip_type)
+        .count(count)
+        .brightness(255)
+        .build();
+    let mut builder = rs_ws281x::ControllerBuilder::new();
+    builder.freq(800_000).channel(0, channel);
+    let mut channels = 1;
+    // the second PWM channel drives a strip that continues the first one
+    if let Some(count) = args.value_of("led_count2") {
+        let pin = u8::from_str(args.value_of("led_pin2").unwrap()).unwrap() as i32;
+        let channel = rs_ws281x::ChannelBuilder::new()
+            .pin(pin)
+            .strip_type(strip_type)
+            .count(u16::from_str(count).unwrap() as i32)
+            .brightness(255)
+            .build();
+        builder.channel(1, channel);
+        channels = 2;
+    }
+    let ctl = PiController::new(builder.build().unwrap(), channels).unwrap();
+    WithPixelFormats::new(ctl, vec![format; channels]).unwrap()
 }
-fn render<R: Receiver, C: Controller>(mut renderer: Renderer<R, C>, verbose: u8) {
-    renderer.blend = 3;
+fn renderer<R: Receiver>(
+    recv: R,
+    args: &ArgMatches,
+    verbose: u8,
+    color_map: ColorMap,
+) -> Renderer<R, WithPixelFormats<PiController>> {
+    let mut renderer = Renderer::new(recv, controller(args));
+    renderer.color_map = color_map;
+    renderer.transition = Duration::from_millis(50);
+    renderer.calibration = match args.value_of("calibration") {
+        Some(path) => Calibration::load(path).unwrap(),
+        None => Calibration::default(),
+    };
+    renderer.power_limit = args.value_of("power_budget").map(|ma| PowerLimit {
+        budget_ma: f32::from_str(ma).unwrap(),
+        ..PowerLimit::default()
+    });
+    renderer.flash_limit = args.value_of("flash_limit").map(|hz| FlashLimiter {
+        max_flashes: f32::from_str(hz).unwrap(),
+        ..FlashLimiter::default()
+    });
+    let delay = u64::from_str(args.value_of("delay").unwrap()).unwrap();
+    renderer.set_presentation_delay(Duration::from_millis(delay));
+    renderer.idle = match args.value_of("idle").unwrap() {
+        "dark" => Idle::Dark,
+        "hold" => Idle::Hold,
+        "fade" => Idle::FadeOut,
+        _ => unreachable!(),
+    };
     renderer.verbose = verbose;
     renderer.color_map[2] = Color::YELLOW;
     renderer.color_map[3] = Color::GREEN;
     renderer.color_map[4] = Color::BLUE;
-    panic!("Rendering quit: {:?}", renderer.update_leds_loop(60.0));
+    renderer
 }
 
 fn parser<'a, 'b>() -> App<'a, 'b> {
@@ -151,6 +203,36 @@ fn parser<'a, 'b>() -> App<'a, 'b> {
                 })
                 .default_value("288"),
         )
+        .arg(
+            Arg::with_name("strip")
+                .long("strip")
+                .value_name("TYPE")
+                .possible_values(&["ws2812", "sk6812w"])
+                .help("The type of LEDs on the strips.")
+                .default_value("ws2812")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("led_pin2")
+                .long("pin2")
+                .value_name("PIN")
+                .help("The pin of the strip on the second PWM channel.")
+                .takes_value(true)
+                .validator(|s| u8::from_str(&s).map(|_| ()).map_err(|e| format!("{:?}", e)))
+                .default_value("13"),
+        )
+        .arg(
+            Arg::with_name("led_count2")
+                .long("count2")
+                .value_name("COUNT")
+                .help("Enables a strip on the second PWM channel with the given number of LEDs.")
+                .takes_value(true)
+                .validator(|s| {
+                    NonZeroU16::from_str(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{:?}", e))
+                }),
+        )
         .arg(
             Arg::with_name("spi")
                 .short("s")
@@ -183,6 +265,62 @@ fn parser<'a, 'b>() -> App<'a, 'b> {
                         .map_err(|e| format!("{:?}", e))
                 }),
         )
+        .arg(
+            Arg::with_name("calibration")
+                .long("calibration")
+                .value_name("FILE")
+                .help("Loads the gamma and white balance of the LEDs from a config file.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("power_budget")
+                .long("power-budget")
+                .value_name("MILLIAMPS")
+                .help("Dims frames that would draw more current than the supply can deliver.")
+                .takes_value(true)
+                .validator(|s| {
+                    f32::from_str(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{:?}", e))
+                }),
+        )
+        .arg(
+            Arg::with_name("flash_limit")
+                .long("flash-limit")
+                .value_name("HZ")
+                .help("Attenuates flashes faster than the given rate for photosensitive safety.")
+                .takes_value(true)
+                .validator(|s| {
+                    f32::from_str(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{:?}", e))
+                }),
+        )
+        .arg(
+            Arg::with_name("idle")
+                .long("idle")
+                .value_name("BEHAVIOR")
+                .possible_values(&["dark", "hold", "fade"])
+                .help("Controls what is shown when no packets have been received for a while.")
+                .default_value("dark")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("delay")
+                .long("delay")
+                .value_name("MILLIS")
+                .help(
+                    "Delays the lights to align them with the latency of the sound system. \
+                     Senders can't query it over the air, so they must be told separately.",
+                )
+                .takes_value(true)
+                .validator(|s| {
+                    u64::from_str(&s)
+                        .map(|_| ())
+                        .map_err(|e| format!("{:?}", e))
+                })
+                .default_value("0"),
+        )
         .arg(
             Arg::with_name("verbose")
                 .short("v")
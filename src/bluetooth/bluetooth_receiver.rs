@@ -1,15 +1,15 @@
 use std::time::{Duration, Instant};
 
-use async_std::future::timeout;
-use async_std::task::block_on;
+use crate::rt::{block_on, timeout};
 
 use gatt::server::{AppWorker, Application};
 use rustable::gatt;
 use rustable::Adapter;
 
-use crate::{Error, LedMsg, Receiver};
+use crate::{AsyncReceiver, Error, LedMsg, Receiver};
 use btutils::messaging::{MsgChannelServ, ServerOptions};
 use btutils::timing::TimeService;
+use futures::future::BoxFuture;
 
 pub struct BluetoothReceiver {
     app: AppWorker,
@@ -44,7 +44,7 @@ impl Receiver for BluetoothReceiver {
         loop {
             let to_dur = deadline.saturating_duration_since(Instant::now());
             return match block_on(timeout(to_dur, self.msg.recv_msg())) {
-                Ok(Ok(data)) => match LedMsg::deserialize(&data, self.cur_time()) {
+                Ok(Ok(data)) => match LedMsg::deserialize(&data, self.time.get_time()) {
                     Ok(msgs) => Ok(msgs),
                     Err(_) => continue,
                 },
@@ -56,16 +56,25 @@ impl Receiver for BluetoothReceiver {
         }
     }
     fn recv(&mut self) -> Result<Vec<LedMsg>, Error> {
-        loop {
-            return match block_on(self.msg.recv_msg()) {
-                Ok(data) => match LedMsg::deserialize(&data, self.cur_time()) {
-                    Ok(msgs) => Ok(msgs),
-                    Err(_) => continue,
-                },
-                Err(_) => Err(Error::Unrecoverable(
-                    "BT message service has panicked!".into(),
-                )),
-            };
-        }
+        block_on(AsyncReceiver::recv(self))
+    }
+}
+
+impl AsyncReceiver for BluetoothReceiver {
+    fn cur_time(&self) -> u64 {
+        self.time.get_time()
+    }
+    fn recv(&mut self) -> BoxFuture<'_, Result<Vec<LedMsg>, Error>> {
+        Box::pin(async move {
+            loop {
+                let data =
+                    self.msg.recv_msg().await.map_err(|_| {
+                        Error::Unrecoverable("BT message service has panicked!".into())
+                    })?;
+                if let Ok(msgs) = LedMsg::deserialize(&data, self.time.get_time()) {
+                    return Ok(msgs);
+                }
+            }
+        })
     }
 }
@@ -1,7 +1,8 @@
-use async_std::task::block_on;
+use crate::rt::block_on;
 use futures::future::join;
 
-use crate::{Error, LedMsg, Sender as LECPSender};
+use crate::{AsyncSender, Error, LedMsg, Sender as LECPSender};
+use futures::future::BoxFuture;
 
 use rustable::{Adapter, MAC};
 
@@ -103,11 +104,33 @@ impl BluetoothSender {
 
 impl LECPSender for BluetoothSender {
     fn send(&mut self, msgs: &mut [LedMsg], is_time_offset: bool) -> Result<(), Error> {
+        block_on(AsyncSender::send(self, msgs, is_time_offset))
+    }
+    fn get_time(&self) -> u64 {
+        self.time.get_time()
+    }
+}
+
+impl AsyncSender for BluetoothSender {
+    fn send<'a>(
+        &'a mut self,
+        msgs: &'a mut [LedMsg],
+        is_time_offset: bool,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { self.send_async(msgs, is_time_offset).await })
+    }
+    fn get_time(&self) -> u64 {
+        self.time.get_time()
+    }
+}
+
+impl BluetoothSender {
+    async fn send_async(&mut self, msgs: &mut [LedMsg], is_time_offset: bool) -> Result<(), Error> {
         let mut out_buf = [0; 512];
         let mtu = self.msg.get_out_mtu();
         let out_buf = &mut out_buf[..mtu as usize];
         let mut msgs_sent = 0;
-        let cur_time = self.get_time();
+        let cur_time = self.time.get_time();
         if is_time_offset {
             for msg in msgs.iter_mut() {
                 msg.time = cur_time.wrapping_add(msg.time);
@@ -117,11 +140,8 @@ impl LECPSender for BluetoothSender {
             let to_send = &msgs[msgs_sent..];
             let (sent, used) = LedMsg::serialize(to_send, out_buf, cur_time);
             msgs_sent += sent;
-            block_on(self.msg.send_msg(&out_buf[..used])).expect("Err unimplemented");
+            self.msg.send_msg(&out_buf[..used]).await?;
         }
         Ok(())
     }
-    fn get_time(&self) -> u64 {
-        self.time.get_time()
-    }
 }
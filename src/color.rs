@@ -1,6 +1,16 @@
 /// Defines the `Color` and `ColorMap` that are used to set colors on the
 /// receiver.
-use std::ops::{Deref, DerefMut, Mul, MulAssign};
+use crate::Error;
+use std::ops::{Add, Deref, DerefMut, Mul, MulAssign};
+use std::path::Path;
+use std::str::FromStr;
+/// An RGBA color.
+///
+/// The color channels are premultiplied by `alpha`, the opacity of the color.
+/// A color with an `alpha` of zero is purely additive light when composited with
+/// [`BlendMode::AlphaOver`], while an `alpha` of 255 completely covers the layers below it.
+///
+/// [`BlendMode::AlphaOver`]: ./enum.BlendMode.html#variant.AlphaOver
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub red: u8,
@@ -24,6 +34,7 @@ impl Mul<f32> for Color {
         self.red = (self.red as f32 * rhs).round().min(255.0) as u8;
         self.blue = (self.blue as f32 * rhs).round().min(255.0) as u8;
         self.green = (self.green as f32 * rhs).round().min(255.0) as u8;
+        self.alpha = (self.alpha as f32 * rhs).round().min(255.0) as u8;
         self
     }
 }
@@ -33,6 +44,7 @@ impl Mul<f64> for Color {
         self.red = (self.red as f64 * rhs).round().min(255.0) as u8;
         self.blue = (self.blue as f64 * rhs).round().min(255.0) as u8;
         self.green = (self.green as f64 * rhs).round().min(255.0) as u8;
+        self.alpha = (self.alpha as f64 * rhs).round().min(255.0) as u8;
         self
     }
 }
@@ -117,6 +129,31 @@ impl Color {
         blue: 128,
         alpha: 0,
     };
+    /// Returns the fully saturated color at `hue` on the color wheel.
+    /// `hue` is in the range [0, 1), where 0 is red, 1/3 is green and 2/3 is blue.
+    pub fn wheel(hue: f32) -> Self {
+        let h = hue.rem_euclid(1.0) * 6.0;
+        let x = ((1.0 - (h % 2.0 - 1.0).abs()) * 255.0).round() as u8;
+        let (red, green, blue) = match h as u8 {
+            0 => (255, x, 0),
+            1 => (x, 255, 0),
+            2 => (0, 255, x),
+            3 => (0, x, 255),
+            4 => (x, 0, 255),
+            _ => (255, 0, x),
+        };
+        Color {
+            red,
+            green,
+            blue,
+            alpha: 0,
+        }
+    }
+    /// Returns the value of the brightest channel as a fraction of full brightness.
+    #[inline]
+    pub fn brightness(&self) -> f32 {
+        self.red.max(self.green).max(self.blue) as f32 / 255.0
+    }
     #[inline]
     pub fn from_bgra(color: [u8; 4]) -> Self {
         Color {
@@ -128,11 +165,11 @@ impl Color {
     }
     #[inline]
     pub fn to_rgb(&self) -> [u8; 3] {
-        [self.red, self.blue, self.green]
+        [self.red, self.green, self.blue]
     }
     #[inline]
     pub fn to_rgba(&self) -> [u8; 4] {
-        [self.red, self.blue, self.green, self.alpha]
+        [self.red, self.green, self.blue, self.alpha]
     }
     #[inline]
     pub fn to_bgr(&self) -> [u8; 3] {
@@ -150,6 +187,250 @@ impl Default for Color {
         Color::BLACK
     }
 }
+
+/// Controls how a layer is composited onto the layers below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BlendMode {
+    /// Adds the layer's channels to those below it.
+    Add = 0,
+    /// Draws the layer over those below it, using its alpha as its opacity.
+    AlphaOver = 1,
+    /// Multiplies the color channels below by the layer's color channels.
+    /// Useful for masking and tinting.
+    Multiply = 2,
+    /// Takes the brightest of the layer's and the lower layers' channels.
+    Max = 3,
+    /// Replaces the lower layers with this layer.
+    Replace = 4,
+}
+impl Default for BlendMode {
+    #[inline]
+    fn default() -> Self {
+        BlendMode::Add
+    }
+}
+impl BlendMode {
+    pub fn from_u8(v: u8) -> Result<Self, Error> {
+        Ok(match v {
+            0 => BlendMode::Add,
+            1 => BlendMode::AlphaOver,
+            2 => BlendMode::Multiply,
+            3 => BlendMode::Max,
+            4 => BlendMode::Replace,
+            v => {
+                return Err(Error::BadInput(format!(
+                    "Unknown blend mode was given: {:#04X}",
+                    v
+                )))
+            }
+        })
+    }
+    /// Composites `src` on top of `dst`.
+    pub fn blend(self, dst: ColorF, src: ColorF) -> ColorF {
+        match self {
+            BlendMode::Add => ColorF {
+                red: dst.red + src.red,
+                green: dst.green + src.green,
+                blue: dst.blue + src.blue,
+                alpha: (dst.alpha + src.alpha).min(1.0),
+            },
+            BlendMode::AlphaOver => {
+                let inv = 1.0 - src.alpha.min(1.0);
+                ColorF {
+                    red: src.red + dst.red * inv,
+                    green: src.green + dst.green * inv,
+                    blue: src.blue + dst.blue * inv,
+                    alpha: src.alpha + dst.alpha * inv,
+                }
+            }
+            BlendMode::Multiply => ColorF {
+                red: dst.red * src.red,
+                green: dst.green * src.green,
+                blue: dst.blue * src.blue,
+                alpha: dst.alpha,
+            },
+            BlendMode::Max => ColorF {
+                red: dst.red.max(src.red),
+                green: dst.green.max(src.green),
+                blue: dst.blue.max(src.blue),
+                alpha: dst.alpha.max(src.alpha),
+            },
+            BlendMode::Replace => src,
+        }
+    }
+}
+
+/// A [`Color`] with `f32` channels, where 1.0 is full brightness.
+///
+/// Frames are composited using `ColorF` so that scaling and blending don't lose precision,
+/// and are only quantized to 8-bit when output to the LEDs.
+/// Channels may exceed 1.0 while compositing and are clamped when quantized.
+///
+/// [`Color`]: ./struct.Color.html
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ColorF {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+impl ColorF {
+    pub const BLACK: ColorF = ColorF {
+        red: 0.0,
+        green: 0.0,
+        blue: 0.0,
+        alpha: 0.0,
+    };
+    /// Linearly interpolates from `self` to `other`, where a `t` of 0 is `self` and 1 is `other`.
+    #[inline]
+    pub fn lerp(self, other: ColorF, t: f32) -> Self {
+        self * (1.0 - t) + other * t
+    }
+    /// Returns the value of the brightest channel.
+    #[inline]
+    pub fn brightness(&self) -> f32 {
+        self.red.max(self.green).max(self.blue)
+    }
+    /// Returns the relative luminance of the color, as perceived by the eye.
+    #[inline]
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+    /// Rounds the color to the nearest `Color`.
+    #[inline]
+    pub fn to_color(&self) -> Color {
+        let q = |v: f32| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color {
+            red: q(self.red),
+            green: q(self.green),
+            blue: q(self.blue),
+            alpha: q(self.alpha),
+        }
+    }
+}
+impl From<Color> for ColorF {
+    #[inline]
+    fn from(c: Color) -> Self {
+        ColorF {
+            red: c.red as f32 / 255.0,
+            green: c.green as f32 / 255.0,
+            blue: c.blue as f32 / 255.0,
+            alpha: c.alpha as f32 / 255.0,
+        }
+    }
+}
+impl Mul<f32> for ColorF {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: f32) -> Self::Output {
+        ColorF {
+            red: self.red * rhs,
+            green: self.green * rhs,
+            blue: self.blue * rhs,
+            alpha: self.alpha * rhs,
+        }
+    }
+}
+impl Add for ColorF {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        ColorF {
+            red: self.red + rhs.red,
+            green: self.green + rhs.green,
+            blue: self.blue + rhs.blue,
+            alpha: self.alpha + rhs.alpha,
+        }
+    }
+}
+
+/// Corrects composited colors for the response of the LEDs before they are output.
+///
+/// Each channel is raised to the power of its `gamma` and then scaled by its `gain`,
+/// so that colors appear perceptually linear and white is balanced for a particular batch of LEDs.
+/// Channels are ordered red, green, blue.
+///
+/// A calibration can be parsed from a config with lines of the form `<key> = <values>`,
+/// where the key is `gamma` or `gain` and the values are either one value for all of
+/// the channels or three values, one for each channel.
+/// Empty lines and lines starting with `#` are ignored. For example:
+/// ```text
+/// # batch 2 runs blue
+/// gamma = 2.2
+/// gain = 1.0 0.85 0.7
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub gamma: [f32; 3],
+    pub gain: [f32; 3],
+}
+impl Default for Calibration {
+    /// Returns the identity calibration, which leaves colors unchanged.
+    fn default() -> Self {
+        Calibration {
+            gamma: [1.0; 3],
+            gain: [1.0; 3],
+        }
+    }
+}
+impl Calibration {
+    /// Reads a calibration from the config file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(|e| Error::Misc(format!("Failed to read {}: {}", path.display(), e)))?
+            .parse()
+    }
+    /// Applies the calibration to `color`. Alpha is left unchanged.
+    #[inline]
+    pub fn apply(&self, color: ColorF) -> ColorF {
+        let ch = |v: f32, i: usize| v.clamp(0.0, 1.0).powf(self.gamma[i]) * self.gain[i];
+        ColorF {
+            red: ch(color.red, 0),
+            green: ch(color.green, 1),
+            blue: ch(color.blue, 2),
+            alpha: color.alpha,
+        }
+    }
+}
+impl FromStr for Calibration {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = Calibration::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |msg: &str| Error::BadInput(format!("Calibration line {}: {}", i + 1, msg));
+            let mut split = line.splitn(2, '=');
+            let key = split.next().unwrap().trim();
+            let values = split.next().ok_or_else(|| bad("Expected '='."))?;
+            let values = values
+                .split_whitespace()
+                .map(f32::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| bad(&e.to_string()))?;
+            if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+                return Err(bad("Values must be non-negative."));
+            }
+            let values = match values[..] {
+                [v] => [v; 3],
+                [r, g, b] => [r, g, b],
+                _ => return Err(bad("Expected one or three values.")),
+            };
+            match key {
+                "gamma" => ret.gamma = values,
+                "gain" => ret.gain = values,
+                _ => return Err(bad(&format!("Unknown key: {}", key))),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[derive(Clone)]
 pub struct ColorMap([Color; 256]);
 
 impl Deref for ColorMap {
@@ -1,80 +1,725 @@
-use crate::color::Color;
 use crate::color::ColorMap;
+use crate::color::{BlendMode, Calibration, ColorF};
+use crate::easing::Easing;
+use crate::effects;
+use crate::layout::{Direction, Layout, Segment};
+use crate::noise::Noise;
+use crate::pixel::PixelFormat;
+use crate::power::{PowerEstimate, PowerLimit};
+use crate::rt;
+use crate::safety::FlashLimiter;
+use crate::stats::{FrameSchedule, FrameStats, Overrun};
 use crate::Error;
-use crate::{Command, LedMsg, Receiver};
-use std::thread::sleep;
+use crate::{AsyncReceiver, Command, LedMsg, Receiver};
+use futures::future::{select, Either};
+use futures::FutureExt;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{sleep, Builder, JoinHandle};
 use std::time::{Duration, Instant};
 
 #[cfg(feature = "rpi")]
 pub use rs_ws281x;
 
+/// Drives the LEDs.
+///
+/// A controller has one or more outputs, such as the PWM channels of a driver, that each
+/// drive their own strip. The [`Renderer`] numbers the LEDs of the outputs one after another,
+/// so the first LED of the second output follows the last LED of the first output,
+/// and segments of the [`Layout`] can span outputs.
+///
+/// Each output stores its LEDs in a [`PixelFormat`], which is `PixelFormat::BGRX` unless
+/// the controller implements [`pixel_format`]. The renderer writes the LEDs through
+/// [`output_bytes_mut`], so any number of bytes per LED is supported, while [`leds`] and
+/// [`output_leds`] are only usable with formats that have four bytes per LED.
+///
+/// [`Renderer`]: ./struct.Renderer.html
+/// [`Layout`]: ../layout/struct.Layout.html
+/// [`PixelFormat`]: ../pixel/struct.PixelFormat.html
+/// [`pixel_format`]: #method.pixel_format
+/// [`output_bytes_mut`]: #method.output_bytes_mut
+/// [`leds`]: #method.leds
+/// [`output_leds`]: #method.output_leds
 pub trait Controller {
-    fn leds_mut(&mut self) -> &mut [[u8; 4]];
-    fn leds(&self) -> &[[u8; 4]];
-    fn render(&mut self);
+    /// Shows the LEDs of every output.
+    /// Failures should be returned as `Error::Controller` so that the renderer's frame loops
+    /// retry them, see [`RenderRetry`](./struct.RenderRetry.html).
+    fn render(&mut self) -> Result<(), Error>;
+    /// Returns the bytes of the LEDs of `output`, laid out in its pixel format.
+    /// `output` must be less than [`outputs`](#method.outputs).
+    fn output_bytes(&self, output: usize) -> &[u8];
+    /// Returns the bytes of the LEDs of `output`, laid out in its pixel format.
+    /// `output` must be less than [`outputs`](#method.outputs).
+    fn output_bytes_mut(&mut self, output: usize) -> &mut [u8];
+    /// Returns the number of outputs.
+    #[inline]
+    fn outputs(&self) -> usize {
+        1
+    }
+    /// Returns the pixel format of `output`.
+    #[inline]
+    fn pixel_format(&self, _output: usize) -> PixelFormat {
+        PixelFormat::default()
+    }
+    /// Returns the number of LEDs of `output`.
+    fn output_len(&self, output: usize) -> usize {
+        self.output_bytes(output).len() / self.pixel_format(output).bytes_per_led()
+    }
+    /// Returns the total number of LEDs across all outputs.
+    fn led_count(&self) -> usize {
+        (0..self.outputs()).map(|o| self.output_len(o)).sum()
+    }
+    /// Returns the LEDs of `output` as groups of four bytes.
+    ///
+    /// # Panics
+    /// Panics if the pixel format of `output` doesn't have four bytes per LED.
+    fn output_leds(&self, output: usize) -> &[[u8; 4]] {
+        assert_four_bytes(self.pixel_format(output));
+        self.output_bytes(output).as_chunks().0
+    }
+    /// Returns the LEDs of `output` as groups of four bytes.
+    ///
+    /// # Panics
+    /// Panics if the pixel format of `output` doesn't have four bytes per LED.
+    fn output_leds_mut(&mut self, output: usize) -> &mut [[u8; 4]] {
+        assert_four_bytes(self.pixel_format(output));
+        self.output_bytes_mut(output).as_chunks_mut().0
+    }
+    /// Returns the LEDs of the first output. See [`output_leds`](#method.output_leds).
+    #[inline]
+    fn leds(&self) -> &[[u8; 4]] {
+        self.output_leds(0)
+    }
+    /// Returns the LEDs of the first output. See [`output_leds_mut`](#method.output_leds_mut).
+    #[inline]
+    fn leds_mut(&mut self) -> &mut [[u8; 4]] {
+        self.output_leds_mut(0)
+    }
+}
+fn assert_four_bytes(format: PixelFormat) {
+    assert_eq!(
+        format.bytes_per_led(),
+        4,
+        "The LEDs of {:?} aren't four bytes, use output_bytes instead.",
+        format
+    );
 }
 
+/// Overrides the pixel formats of a controller's outputs,
+/// such as to drive SK6812 RGBW strips with `rs_ws281x` using `PixelFormat::BGRW`.
+/// Outputs without a format in `formats` keep the controller's own.
+pub struct WithPixelFormats<C: Controller> {
+    ctl: C,
+    formats: Vec<PixelFormat>,
+}
+impl<C: Controller> WithPixelFormats<C> {
+    /// Overrides the format of each of the first `formats.len()` outputs of `ctl`.
+    /// Returns an error if there are more formats than outputs, or if a format doesn't have
+    /// the same number of bytes per LED as the controller's own format for the output.
+    pub fn new(ctl: C, formats: Vec<PixelFormat>) -> Result<Self, Error> {
+        if formats.len() > ctl.outputs() {
+            return Err(Error::BadInput(format!(
+                "{} pixel formats were given for a controller with {} outputs.",
+                formats.len(),
+                ctl.outputs()
+            )));
+        }
+        for (output, format) in formats.iter().enumerate() {
+            let own = ctl.pixel_format(output);
+            if format.bytes_per_led() != own.bytes_per_led() {
+                return Err(Error::BadInput(format!(
+                    "{:?} has {} bytes per LED, but output {} has {}.",
+                    format,
+                    format.bytes_per_led(),
+                    output,
+                    own.bytes_per_led()
+                )));
+            }
+        }
+        Ok(WithPixelFormats { ctl, formats })
+    }
+    #[inline]
+    pub fn get_ref(&self) -> &C {
+        &self.ctl
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.ctl
+    }
+    #[inline]
+    pub fn formats(&self) -> &[PixelFormat] {
+        &self.formats
+    }
+}
+impl<C: Controller> Controller for WithPixelFormats<C> {
+    #[inline]
+    fn render(&mut self) -> Result<(), Error> {
+        self.ctl.render()
+    }
+    #[inline]
+    fn output_bytes(&self, output: usize) -> &[u8] {
+        self.ctl.output_bytes(output)
+    }
+    #[inline]
+    fn output_bytes_mut(&mut self, output: usize) -> &mut [u8] {
+        self.ctl.output_bytes_mut(output)
+    }
+    #[inline]
+    fn outputs(&self) -> usize {
+        self.ctl.outputs()
+    }
+    #[inline]
+    fn pixel_format(&self, output: usize) -> PixelFormat {
+        match self.formats.get(output) {
+            Some(format) => *format,
+            None => self.ctl.pixel_format(output),
+        }
+    }
+}
+
+/// Drives the first PWM channel of the Pi. Use [`PiController`](./struct.PiController.html)
+/// to drive both channels.
 #[cfg(feature = "rpi")]
 impl Controller for rs_ws281x::Controller {
     #[inline]
-    fn leds_mut(&mut self) -> &mut [[u8; 4]] {
-        self.leds_mut(0)
+    fn render(&mut self) -> Result<(), Error> {
+        self.render()
+            .map_err(|e| Error::Controller(format!("rs_ws281x failed to render: {:?}", e)))
     }
     #[inline]
-    fn leds(&self) -> &[[u8; 4]] {
-        self.leds(0)
+    fn output_bytes(&self, output: usize) -> &[u8] {
+        assert_eq!(output, 0, "Controller only has one output.");
+        self.leds(0).as_flattened()
+    }
+    #[inline]
+    fn output_bytes_mut(&mut self, output: usize) -> &mut [u8] {
+        assert_eq!(output, 0, "Controller only has one output.");
+        self.leds_mut(0).as_flattened_mut()
+    }
+}
+/// Drives the PWM channels of the Pi that were configured, which each become an output.
+#[cfg(feature = "rpi")]
+pub struct PiController {
+    ctl: rs_ws281x::Controller,
+    channels: usize,
+}
+#[cfg(feature = "rpi")]
+impl PiController {
+    /// Creates a controller for the first `channels` channels of `ctl`,
+    /// which must all have been configured.
+    pub fn new(ctl: rs_ws281x::Controller, channels: usize) -> Result<Self, Error> {
+        if channels == 0 || channels > 2 {
+            return Err(Error::BadInput(format!(
+                "The Pi has one or two PWM channels, not {}.",
+                channels
+            )));
+        }
+        Ok(PiController { ctl, channels })
+    }
+    #[inline]
+    pub fn get_ref(&self) -> &rs_ws281x::Controller {
+        &self.ctl
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut rs_ws281x::Controller {
+        &mut self.ctl
+    }
+}
+#[cfg(feature = "rpi")]
+impl Controller for PiController {
+    #[inline]
+    fn render(&mut self) -> Result<(), Error> {
+        Controller::render(&mut self.ctl)
+    }
+    #[inline]
+    fn output_bytes(&self, output: usize) -> &[u8] {
+        assert!(
+            output < self.channels,
+            "Channel {} wasn't configured.",
+            output
+        );
+        self.ctl.leds(output).as_flattened()
     }
     #[inline]
-    fn render(&mut self) {
-        self.render().unwrap()
+    fn output_bytes_mut(&mut self, output: usize) -> &mut [u8] {
+        assert!(
+            output < self.channels,
+            "Channel {} wasn't configured.",
+            output
+        );
+        self.ctl.leds_mut(output).as_flattened_mut()
+    }
+    #[inline]
+    fn outputs(&self) -> usize {
+        self.channels
     }
 }
+/// Tracks an element as it transitions between successive messages.
+#[derive(Default)]
+struct ElementState {
+    /// The message currently shown by the element.
+    cur: Option<LedMsg>,
+    /// The blend mode of the last message shown.
+    mode: BlendMode,
+    /// The last frame rendered for the element. LEDs the element doesn't cover are `None`.
+    layer: Vec<Option<ColorF>>,
+    /// The frame being faded from. Empty if the element isn't transitioning.
+    from: Vec<Option<ColorF>>,
+    /// The start time and duration of the transition in microseconds.
+    start: u64,
+    duration: u64,
+    easing: Easing,
+}
+
+/// Renders `msg` into `layer`.
+/// `cursor` is where the next `FlatStack` element starts in `segment`, in fractional positions.
+fn render_msg(
+    msg: &LedMsg,
+    color_map: &ColorMap,
+    cur_time: u64,
+    segment: &Segment,
+    cursor: &mut f32,
+    layer: &mut [Option<ColorF>],
+) {
+    let leds = layer.len();
+    let len = segment.positions(leds);
+    let color = ColorF::from(color_map[msg.color as usize]);
+    match msg.cmd {
+        Command::FlatStack(v) => {
+            let start = *cursor;
+            let ratio = len as f32 / 256.0;
+            let end = (len as f32).min(start + (v as f32 + 1.0) * ratio);
+            for pos in start.floor() as usize..end.ceil() as usize {
+                let c = color * effects::coverage(pos, start, end);
+                segment.for_each_led(pos, leds, |led| layer[led] = Some(c));
+            }
+            *cursor = end;
+        }
+        cmd => {
+            // messages from the future are held at their first frame
+            let elapsed = (cur_time.wrapping_sub(msg.time) as i64).max(0) as u64;
+            let noise = Noise::from_msg(msg);
+            for pos in 0..len {
+                let c = effects::eval(cmd, color, &noise, msg.easing, elapsed, pos, len);
+                segment.for_each_led(pos, leds, |led| layer[led] = c);
+            }
+        }
+    }
+}
+
+/// What the renderer shows when no packets have been received for a while.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Idle {
+    /// Keeps rendering the last messages until they expire, which leaves the LEDs dark.
+    Dark,
+    /// Holds the last frame shown before the receiver went idle.
+    Hold,
+    /// Fades the LEDs to black.
+    FadeOut,
+    /// Renders the message across the whole strip as an ambient animation.
+    /// The time of the message is replaced with the time the receiver went idle.
+    Animate(LedMsg),
+}
+impl Default for Idle {
+    #[inline]
+    fn default() -> Self {
+        Idle::Dark
+    }
+}
+
+/// How the renderer chooses between messages from different sources addressing the same element.
+/// See [`LedMsg::source`](../struct.LedMsg.html#structfield.source).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// The last message received is shown, whichever source sent it.
+    Latest,
+    /// The brightest of each source's last message is shown.
+    /// A message's brightness is the luminance of its color scaled by its `Flat` value.
+    Highest,
+    /// The last message of the source with the highest priority is shown.
+    /// See [`Renderer::source_priority`](./struct.Renderer.html#structfield.source_priority).
+    Priority,
+    /// The first source to address the element locks it, and messages from other sources
+    /// are ignored until the lock holder's messages have expired.
+    /// If several sources address a free element in the same frame, the source of the
+    /// oldest message gets the lock.
+    Exclusive,
+}
+impl Default for MergePolicy {
+    #[inline]
+    fn default() -> Self {
+        MergePolicy::Latest
+    }
+}
+/// Returns the brightness used by `MergePolicy::Highest` at `cur_time`.
+fn msg_level(msg: &LedMsg, color_map: &ColorMap, cur_time: u64) -> f32 {
+    let color = ColorF::from(color_map[msg.color as usize]);
+    let level = match msg.cmd {
+        Command::Null => 0.0,
+        Command::Flat(v) => v as f32 / 255.0,
+        // the share of the segment the element fills
+        Command::FlatStack(v) => (v as f32 + 1.0) / 256.0,
+        Command::PulseLinear(_) | Command::PulseQuadratic(_) => {
+            // pulses are as bright as they are at `cur_time`, not when they started
+            let elapsed = (cur_time.wrapping_sub(msg.time) as i64).max(0) as u64;
+            let noise = Noise::from_msg(msg);
+            return effects::eval(msg.cmd, color, &noise, msg.easing, elapsed, 0, 1)
+                .map_or(0.0, |c| c.luminance());
+        }
+        _ => 1.0,
+    };
+    color.luminance() * level
+}
+
+/// How the frame loops respond to the controller failing to render a frame.
+///
+/// After a failure, the loop waits for the backoff before rendering the next frame.
+/// The backoff doubles with each consecutive failure, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderRetry {
+    /// The number of consecutive failures after which the loop returns the error.
+    /// If `None`, the loop retries forever.
+    pub max_failures: Option<u32>,
+    /// The wait after the first failure.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+impl Default for RenderRetry {
+    fn default() -> Self {
+        RenderRetry {
+            max_failures: Some(10),
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
 pub struct Renderer<T: Receiver, C: Controller> {
     recv: T,
     ctl: C,
-    work_buf: Vec<[u8; 4]>,
+    work_buf: Vec<ColorF>,
+    /// The frame after the global state and output stages are applied to `work_buf`.
+    out_buf: Vec<ColorF>,
+    /// The quantization error carried to the next frame by temporal dithering.
+    dither_err: Vec<[f32; 4]>,
     msgs: Vec<LedMsg>,
-    pub blend: u8,
+    /// Global messages waiting for their time.
+    globals: Vec<LedMsg>,
+    states: Vec<ElementState>,
+    cursors: Vec<f32>,
+    /// The (element, source) pairs whose last message has been found this frame.
+    seen: HashSet<(u8, u8)>,
+    /// The merge policy of each element.
+    policies: [MergePolicy; 256],
+    /// The priority of each source, used by `MergePolicy::Priority`.
+    /// Higher priorities take precedence. Every source starts at zero.
+    pub source_priority: [u8; 256],
+    /// The source holding each element locked by `MergePolicy::Exclusive`.
+    locks: [Option<u8>; 256],
+    /// Maps the elements onto the LEDs.
+    pub layout: Layout,
+    /// The duration of the crossfade between successive messages for an element.
+    /// Messages with their own `transition` override this.
+    pub transition: Duration,
+    /// The easing curve used for crossfades.
+    /// Messages with their own `easing` override this.
+    pub transition_easing: Easing,
+    /// How far behind the receiver's clock messages are shown.
+    presentation_delay: Duration,
     pub color_map: ColorMap,
+    /// Turns off every LED. Set by `Blackout` messages and cleared by `Resume` messages.
+    pub blackout: bool,
+    /// The master brightness, from 0 to 1, that every LED is scaled by.
+    /// Set by `Dimmer` messages.
+    pub dimmer: f32,
+    /// Holds the last frame instead of rendering new ones.
+    /// Set by `Freeze` messages and cleared by `Resume` messages.
+    pub frozen: bool,
+    /// The behavior when no packets have been received for `idle_timeout`.
+    pub idle: Idle,
+    /// How long after the last packet the receiver goes idle.
+    /// Messages expire after five seconds, so `Idle::Hold` needs a shorter timeout.
+    pub idle_timeout: Duration,
+    /// The duration of the crossfade into and out of the idle behavior.
+    pub idle_fade: Duration,
+    /// The time of the last packet, or of the first frame if none have been received.
+    last_packet: Option<u64>,
+    last_frame: Option<u64>,
+    /// How far the output has faded from live control to the idle frame, from 0 to 1.
+    idle_level: f32,
+    idle_frame: Vec<ColorF>,
+    idle_layer: Vec<Option<ColorF>>,
+    /// The correction applied to each LED after compositing.
+    pub calibration: Calibration,
+    /// Limits the current drawn by the LEDs by dimming frames that would exceed its budget.
+    /// When `None`, frames are neither limited nor estimated.
+    pub power_limit: Option<PowerLimit>,
+    power: PowerEstimate,
+    /// Attenuates flashes that are too frequent to be safe for photosensitive viewers.
+    /// This is applied to the final frame, so it can't be overridden by senders.
+    pub flash_limit: Option<FlashLimiter>,
+    /// What the frame loop does when a frame overruns its period.
+    pub overrun: Overrun,
+    /// The period the frame loop collects statistics over.
+    pub stats_period: Duration,
+    stats: FrameStats,
+    msgs_applied: u64,
+    msgs_expired: u64,
+    /// Enables temporal dithering.
+    /// When enabled, the rounding error of each LED is carried over to the next frame
+    /// so that colors between two 8-bit values are shown by alternating between them.
+    /// This keeps low brightness fades smooth.
+    pub dither: bool,
+    /// How the frame loops retry frames the controller failed to render.
+    pub render_retry: RenderRetry,
+    /// The number of consecutive frames the controller failed to render.
+    render_failures: u32,
+    /// Set when the LEDs have changed since they were last rendered successfully.
+    render_pending: bool,
     pub verbose: u8,
 }
 
 impl<R: Receiver, C: Controller> Renderer<R, C> {
     pub fn new(recv: R, mut ctl: C) -> Self {
-        let work_buf = Vec::with_capacity(ctl.leds().len());
-        let color = Color {
-            red: 1,
-            blue: 1,
-            green: 1,
-            alpha: 0,
-        }
-        .to_bgra();
-        for led in ctl.leds_mut().iter_mut() {
-            *led = color;
+        let work_buf = Vec::with_capacity(ctl.led_count());
+        for output in 0..ctl.outputs() {
+            let format = ctl.pixel_format(output);
+            for led in ctl
+                .output_bytes_mut(output)
+                .chunks_exact_mut(format.bytes_per_led())
+            {
+                format.write([1, 1, 1, 0], led);
+            }
         }
         Renderer {
             work_buf,
+            out_buf: Vec::new(),
+            dither_err: Vec::new(),
             recv,
             ctl,
             msgs: Vec::new(),
-            blend: 0,
+            globals: Vec::new(),
+            states: (0..256).map(|_| ElementState::default()).collect(),
+            cursors: Vec::new(),
+            seen: HashSet::new(),
+            policies: [MergePolicy::default(); 256],
+            source_priority: [0; 256],
+            locks: [None; 256],
+            layout: Layout::default(),
+            transition: Duration::from_secs(0),
+            transition_easing: Easing::default(),
+            presentation_delay: Duration::from_secs(0),
             color_map: ColorMap::default(),
+            blackout: false,
+            dimmer: 1.0,
+            frozen: false,
+            idle: Idle::default(),
+            idle_timeout: Duration::from_secs(2),
+            idle_fade: Duration::from_secs(1),
+            last_packet: None,
+            last_frame: None,
+            idle_level: 0.0,
+            idle_frame: Vec::new(),
+            idle_layer: Vec::new(),
+            calibration: Calibration::default(),
+            power_limit: None,
+            power: PowerEstimate::default(),
+            flash_limit: None,
+            overrun: Overrun::default(),
+            stats_period: Duration::from_secs(5),
+            stats: FrameStats::default(),
+            msgs_applied: 0,
+            msgs_expired: 0,
+            dither: true,
+            render_retry: RenderRetry::default(),
+            render_failures: 0,
+            render_pending: false,
             verbose: 0,
         }
     }
     #[inline]
-    pub fn set_blend(&mut self, blend: u8) {
-        self.blend = blend;
+    pub fn controller(&self) -> &C {
+        &self.ctl
+    }
+    #[inline]
+    pub fn controller_mut(&mut self) -> &mut C {
+        &mut self.ctl
+    }
+    /// Returns the LEDs of `output` in the numbering used by the layout,
+    /// such as for creating a segment that covers one output.
+    pub fn output_range(&self, output: usize) -> Range<usize> {
+        let start = (0..output).map(|o| self.ctl.output_len(o)).sum();
+        start..start + self.ctl.output_len(output)
+    }
+    /// Estimates the current drawn by `out_buf`, with the white of each LED moved to
+    /// the white channel on outputs that have one.
+    fn estimate_power(&self, limit: &PowerLimit) -> f32 {
+        (0..self.ctl.outputs())
+            .map(|output| {
+                let format = self.ctl.pixel_format(output);
+                let leds = &self.out_buf[self.output_range(output)];
+                limit.estimate_channels(leds.iter().map(|c| format.split(*c)))
+            })
+            .sum()
+    }
+    #[inline]
+    pub fn set_transition(&mut self, transition: Duration) {
+        self.transition = transition;
+    }
+    #[inline]
+    pub fn transition(&self) -> Duration {
+        self.transition
+    }
+    /// Delays the time messages are shown at by `delay`, relative to the receiver's clock.
+    /// This aligns the lights with outputs that have their own latency, such as a sound system,
+    /// without changing the senders.
+    /// The receiver is notified so that it can report the delay to senders,
+    /// see [`Sender::presentation_delay`](../trait.Sender.html#method.presentation_delay).
+    pub fn set_presentation_delay(&mut self, delay: Duration) {
+        self.presentation_delay = delay;
+        self.recv.set_presentation_delay(delay);
+    }
+    #[inline]
+    pub fn presentation_delay(&self) -> Duration {
+        self.presentation_delay
+    }
+    /// Sets how messages from different sources addressing `element` are merged.
+    #[inline]
+    pub fn set_merge_policy(&mut self, element: u8, policy: MergePolicy) {
+        self.policies[element as usize] = policy;
+        if policy != MergePolicy::Exclusive {
+            self.locks[element as usize] = None;
+        }
+    }
+    #[inline]
+    pub fn merge_policy(&self, element: u8) -> MergePolicy {
+        self.policies[element as usize]
+    }
+    /// Returns the source that holds `element` locked, if its policy is `MergePolicy::Exclusive`.
+    #[inline]
+    pub fn lock_holder(&self, element: u8) -> Option<u8> {
+        self.locks[element as usize]
+    }
+    /// Returns the time that is being shown, which is the receiver's time minus the presentation delay.
+    #[inline]
+    fn show_time(&self) -> u64 {
+        let delay = self.presentation_delay.as_micros() as u64;
+        self.recv.cur_time().wrapping_sub(delay)
+    }
+    /// Returns the statistics of the frame loop's last completed `stats_period`.
+    #[inline]
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+    /// Returns the estimated current drawn by the last frame.
+    /// This is only updated when `power_limit` is set.
+    #[inline]
+    pub fn power(&self) -> PowerEstimate {
+        self.power
+    }
+    /// Returns the number of consecutive frames the controller failed to render.
+    #[inline]
+    pub fn render_failures(&self) -> u32 {
+        self.render_failures
     }
+    /// Returns `true` if the renderer is showing its idle behavior, or fading into or out of it.
     #[inline]
-    pub fn blend(&self) -> u8 {
-        self.blend
+    pub fn is_idle(&self) -> bool {
+        self.idle_level > 0.0
+    }
+    /// Advances the crossfade between live control and the idle behavior,
+    /// and renders the idle frame for a strip of `len` LEDs.
+    /// Returns how much of the idle frame should be shown.
+    fn update_idle(&mut self, cur_time: u64, len: usize) -> f32 {
+        let last_packet = *self.last_packet.get_or_insert(cur_time);
+        let since = (cur_time.wrapping_sub(last_packet) as i64).max(0);
+        let timeout = self.idle_timeout.as_micros() as i64;
+        let idle = self.idle != Idle::Dark && since >= timeout;
+        // only fade for the time spent in the current state, so that the fade is
+        // independent of the frame rate
+        let in_state = if idle { since - timeout } else { since };
+        let dt = self
+            .last_frame
+            .map_or(0, |t| (cur_time.wrapping_sub(t) as i64).max(0))
+            .min(in_state) as f32;
+        self.last_frame = Some(cur_time);
+        let fade = self.idle_fade.as_micros() as f32;
+        let step = if fade == 0.0 { 1.0 } else { dt / fade };
+        let was_idle = self.idle_level > 0.0;
+        self.idle_level = if idle {
+            (self.idle_level + step).min(1.0)
+        } else {
+            (self.idle_level - step).max(0.0)
+        };
+        if idle && !was_idle && self.verbose >= 1 {
+            eprintln!("No packets for {:?}, going idle.", self.idle_timeout);
+        }
+        if self.idle_level == 0.0 {
+            return 0.0;
+        }
+        match self.idle {
+            Idle::Dark | Idle::FadeOut => {
+                self.idle_frame.clear();
+                self.idle_frame.resize(len, ColorF::BLACK);
+            }
+            Idle::Hold => {
+                if !was_idle || self.idle_frame.len() != len {
+                    self.idle_frame.clear();
+                    self.idle_frame.extend_from_slice(&self.work_buf);
+                    self.idle_frame.resize(len, ColorF::BLACK);
+                }
+            }
+            Idle::Animate(msg) => {
+                let msg = LedMsg {
+                    time: last_packet.wrapping_add(self.idle_timeout.as_micros() as u64),
+                    ..msg
+                };
+                let strip = Segment::new("idle", 0, len, Direction::Forward);
+                self.idle_layer.clear();
+                self.idle_layer.resize(len, None);
+                render_msg(
+                    &msg,
+                    &self.color_map,
+                    cur_time,
+                    &strip,
+                    &mut 0.0,
+                    &mut self.idle_layer,
+                );
+                self.idle_frame.clear();
+                self.idle_frame
+                    .extend(self.idle_layer.iter().map(|c| c.unwrap_or_default()));
+            }
+        }
+        self.transition_easing.apply(self.idle_level)
+    }
+    /// Applies a global command to the renderer's state.
+    fn apply_global(&mut self, cmd: Command) {
+        match cmd {
+            Command::Blackout => self.blackout = true,
+            Command::Dimmer(v) => self.dimmer = v as f32 / 255.0,
+            Command::Freeze => self.frozen = true,
+            Command::Resume => {
+                self.blackout = false;
+                self.frozen = false;
+            }
+            _ => unreachable!(),
+        }
     }
     pub fn update_leds(&mut self) -> Result<(), Error> {
         // append values to list of msg
         loop {
             match self.recv.try_recv() {
-                Ok(msgs) => self.msgs.extend(msgs),
+                Ok(msgs) => {
+                    self.last_packet = Some(self.show_time());
+                    for msg in msgs {
+                        if msg.cmd.is_global() {
+                            self.globals.push(msg);
+                        } else {
+                            self.msgs.push(msg);
+                        }
+                    }
+                }
                 Err(e) => match e {
                     Error::Timeout(_) => break,
                     _ => return Err(e),
@@ -85,17 +730,78 @@ impl<R: Receiver, C: Controller> Renderer<R, C> {
         let mut elements = [None; 256];
         let mut last_active = 0;
         let mut first_active = 256;
-        let cur_time = self.recv.cur_time();
+        let cur_time = self.show_time();
         if self.verbose >= 4 {
             eprintln!("cur_time: {}", cur_time);
         }
+        // apply the global messages that are due, oldest first.
+        // Like other messages, they expire when more than 5 seconds away from now,
+        // so a late or retransmitted packet can't black out or freeze the receiver.
+        let elapsed = |msg: &LedMsg| cur_time.wrapping_sub(msg.time) as i64;
+        let queued = self.globals.len();
+        self.globals.retain(|msg| elapsed(msg).abs() <= 5_000_000);
+        self.msgs_expired += (queued - self.globals.len()) as u64;
+        self.globals
+            .sort_by_key(|msg| std::cmp::Reverse(elapsed(msg)));
+        let due = self
+            .globals
+            .iter()
+            .take_while(|msg| elapsed(msg) >= 0)
+            .count();
+        for i in 0..due {
+            let msg = self.globals[i];
+            if self.verbose >= 1 {
+                eprintln!("global msg: {:?}", msg);
+            }
+            self.apply_global(msg.cmd);
+            self.msgs_applied += 1;
+        }
+        self.globals.drain(..due);
+        // Only the last message from each source is considered for an element.
+        // Unless the policy is `Latest`, these are kept so that another source's message
+        // can be shown when the chosen one expires.
+        let live = |msg: &LedMsg| (cur_time.wrapping_sub(msg.time) as i64).abs() <= 5_000_000;
+        let mut seen = std::mem::take(&mut self.seen);
+        seen.clear();
+        let mut keep = vec![false; self.msgs.len()];
+        // Exclusive elements are claimed by the lock holder while it has messages,
+        // otherwise by the source of the oldest message.
+        let mut claims = [None; 256];
+        for msg in self.msgs.iter().filter(|msg| live(msg)) {
+            let e = msg.element as usize;
+            if self.policies[e] == MergePolicy::Exclusive
+                && (claims[e].is_none() || self.locks[e] == Some(msg.source))
+            {
+                claims[e] = Some(msg.source);
+            }
+        }
+        // whether `msg` should be shown instead of `cur`, which was received after it
+        let prefer = |msg: &LedMsg, cur: &LedMsg| match self.policies[msg.element as usize] {
+            MergePolicy::Latest => false,
+            MergePolicy::Highest => {
+                msg_level(msg, &self.color_map, cur_time)
+                    > msg_level(cur, &self.color_map, cur_time)
+            }
+            MergePolicy::Priority => {
+                self.source_priority[msg.source as usize]
+                    > self.source_priority[cur.source as usize]
+            }
+            MergePolicy::Exclusive => {
+                let holder = claims[msg.element as usize];
+                holder == Some(msg.source) && holder != Some(cur.source)
+            }
+        };
         for (i, msg) in self.msgs.iter().enumerate().rev() {
             if self.verbose >= 3 {
                 eprintln!("msg {}: {:?}", i, msg);
             }
-            if (cur_time.wrapping_sub(msg.time) as i64).abs() <= 5_000_000 {
+            if live(msg) {
                 let e = msg.element as usize;
-                if elements[e].is_none() {
+                if !seen.insert((msg.element, msg.source)) {
+                    continue;
+                }
+                keep[i] = self.policies[e] != MergePolicy::Latest;
+                if elements[e].is_none_or(|c| prefer(msg, &self.msgs[c])) {
                     if e + 1 > last_active {
                         last_active = e + 1;
                     }
@@ -108,11 +814,26 @@ impl<R: Receiver, C: Controller> Renderer<R, C> {
                 eprintln!("ignoring msg {} do to time constraints", i);
             }
         }
-        let mut flat_stack = 0;
-        let leds = self.ctl.leds_mut();
-        let ratio = leds.len() as f32 / 256.0;
-        self.work_buf.clear();
-        self.work_buf.resize(leds.len(), [0; 4]);
+        self.seen = seen;
+        // exclusive elements are locked by the source shown, and unlocked when it has no messages
+        let msgs = &self.msgs;
+        for (e, lock) in self.locks.iter_mut().enumerate() {
+            if self.policies[e] == MergePolicy::Exclusive {
+                let holder = elements[e].map(|m: usize| msgs[m].source);
+                if holder != *lock && self.verbose >= 1 {
+                    eprintln!("element {} locked by source {:?}", e, holder);
+                }
+                *lock = holder;
+            }
+        }
+        let len = self.ctl.led_count();
+        let idle = self.update_idle(cur_time, len);
+        let segments = self.layout.segments();
+        // where the next FlatStack element starts in each segment
+        self.cursors.clear();
+        self.cursors.resize(segments.len(), 0.0);
+        // a frozen renderer keeps showing the last frame it composited
+        let compose = !self.frozen || self.work_buf.len() != len;
         if self.verbose >= 3 && first_active < last_active {
             eprintln!(
                 "active elements [{},{}): {:?}",
@@ -121,52 +842,140 @@ impl<R: Receiver, C: Controller> Renderer<R, C> {
                 &elements[first_active..last_active]
             );
         }
-        for i in first_active..last_active {
-            if let Some(m) = elements[i as usize] {
-                let msg = self.msgs[m];
-                match msg.cmd {
-                    Command::FlatStack(v) => {
-                        let end = leds
-                            .len()
-                            .min(flat_stack + ((v as f32 + 1.0) * ratio).round() as usize);
-                        let color = self.color_map[msg.color as usize].to_bgra();
-                        for j in flat_stack..end {
-                            // add color
-                            for (spt, sps) in self.work_buf[j].iter_mut().zip(color.iter()) {
-                                *spt = spt.saturating_add(*sps);
+        let default_transition = self.transition.as_micros() as u64;
+        let msgs = &self.msgs;
+        if compose {
+            self.work_buf.clear();
+            self.work_buf.resize(len, ColorF::BLACK);
+            for (e, state) in self.states.iter_mut().enumerate() {
+                let target = elements[e].map(|m| msgs[m]);
+                if target != state.cur {
+                    if target.is_some() {
+                        self.msgs_applied += 1;
+                    }
+                    // start a transition from whatever was last shown
+                    std::mem::swap(&mut state.from, &mut state.layer);
+                    state.from.resize(len, None);
+                    state.start = match target {
+                        Some(msg) => msg.time,
+                        None => cur_time,
+                    };
+                    let shaping = target.or(state.cur);
+                    state.duration = shaping
+                        .and_then(|msg| msg.transition)
+                        .map_or(default_transition, |t| t as u64 * 1000);
+                    state.easing = shaping
+                        .and_then(|msg| msg.easing)
+                        .unwrap_or(self.transition_easing);
+                    state.cur = target;
+                }
+                state.layer.clear();
+                if state.cur.is_none() && state.from.is_empty() {
+                    continue;
+                }
+                state.layer.resize(len, None);
+                if let Some(msg) = state.cur {
+                    state.mode = msg.blend_mode;
+                    let seg = self.layout.segment_of(msg.element);
+                    render_msg(
+                        &msg,
+                        &self.color_map,
+                        cur_time,
+                        &segments[seg],
+                        &mut self.cursors[seg],
+                        &mut state.layer,
+                    );
+                }
+                if !state.from.is_empty() {
+                    let elapsed = (cur_time.wrapping_sub(state.start) as i64).max(0) as u64;
+                    if elapsed >= state.duration {
+                        state.from.clear();
+                    } else {
+                        let t = state.easing.apply(elapsed as f32 / state.duration as f32);
+                        for (l, f) in state.layer.iter_mut().zip(state.from.iter()) {
+                            if l.is_some() || f.is_some() {
+                                let (f, to) = (f.unwrap_or_default(), l.unwrap_or_default());
+                                *l = Some(f.lerp(to, t));
                             }
                         }
-                        flat_stack = end;
                     }
-                    _ => unimplemented!(),
+                }
+                for (spt, l) in self.work_buf.iter_mut().zip(state.layer.iter()) {
+                    if let Some(l) = l {
+                        *spt = state.mode.blend(*spt, *l);
+                    }
                 }
             }
         }
         if self.verbose >= 4 {
             eprintln!("work buf: {:?}", self.work_buf);
         }
+        let master = if self.blackout { 0.0 } else { self.dimmer };
+        let calibration = &self.calibration;
+        self.out_buf.clear();
+        if idle > 0.0 {
+            let live = self.work_buf.iter().zip(self.idle_frame.iter());
+            self.out_buf
+                .extend(live.map(|(l, i)| calibration.apply(l.lerp(*i, idle) * master)));
+        } else {
+            self.out_buf
+                .extend(self.work_buf.iter().map(|c| calibration.apply(*c * master)));
+        }
+        if let Some(limit) = self.power_limit {
+            let requested_ma = self.estimate_power(&limit);
+            let scale = limit.scale(requested_ma, self.out_buf.len());
+            if scale < 1.0 {
+                for c in self.out_buf.iter_mut() {
+                    *c = *c * scale;
+                }
+            }
+            self.power = PowerEstimate {
+                requested_ma,
+                output_ma: self.estimate_power(&limit),
+                scale,
+            };
+            if self.verbose >= 3 && scale < 1.0 {
+                eprintln!("power limited: {:?}", self.power);
+            }
+        }
+        if let Some(limit) = &mut self.flash_limit {
+            limit.apply(cur_time, &mut self.out_buf);
+        }
         let mut changed = false;
-        for (led, src) in leds.iter_mut().zip(self.work_buf.iter()) {
-            if self.blend == 0 && *led != *src {
-                *led = *src;
-                changed = true;
-            } else {
-                for (d, s) in led.iter_mut().zip(src.iter()) {
-                    if *d != *s {
-                        let blend = self.blend as u16;
-                        let newval = (((*d as u16 * blend) + *s as u16) / (blend + 1)) as u8;
-                        if newval == *d {
-                            *d += 1;
-                        } else {
-                            *d = newval;
-                        }
-                        changed = true;
+        self.dither_err.resize(len, [0.0; 4]);
+        let dither = self.dither;
+        let mut srcs = self.out_buf.iter().zip(self.dither_err.iter_mut());
+        for output in 0..self.ctl.outputs() {
+            let format = self.ctl.pixel_format(output);
+            let max = format.depth.max() as f32;
+            let size = format.bytes_per_led();
+            let mut buf = [0; 8];
+            let leds = self.ctl.output_bytes_mut(output);
+            for (led, (src, err)) in leds.chunks_exact_mut(size).zip(&mut srcs) {
+                let quantize = |v: f32, err: &mut f32| {
+                    let v = v * max + *err;
+                    let q = v.round().clamp(0.0, max);
+                    if dither {
+                        // only carry errors within a step so clipped values don't build up error
+                        *err = (v - q).clamp(-0.5, 0.5);
                     }
+                    q as u16
+                };
+                // alpha is only used for compositing and must not reach the LEDs
+                let split = format.split(*src);
+                let mut values = [0; 4];
+                for ((v, s), e) in values.iter_mut().zip(split.iter()).zip(err.iter_mut()) {
+                    *v = quantize(*s, e);
+                }
+                format.write(values, &mut buf[..size]);
+                if led != &buf[..size] {
+                    led.copy_from_slice(&buf[..size]);
+                    changed = true;
                 }
             }
-        }
-        if self.verbose >= 4 {
-            eprintln!("Led buf: {:?}", leds)
+            if self.verbose >= 4 {
+                eprintln!("Led buf {}: {:?}", output, leds)
+            }
         }
         /*
         for (i, led) in leds.iter_mut().enumerate() {
@@ -180,18 +989,19 @@ impl<R: Receiver, C: Controller> Renderer<R, C> {
             };
         }
         */
-        if changed {
-            self.ctl.render();
-        }
+        // LEDs that failed to render are rendered again even if they haven't changed since
+        self.render_pending |= changed;
 
         // Prune old msgs
         let mut del = 0;
-        for i in 0..self.msgs.len() {
+        for (i, kept) in keep.into_iter().enumerate() {
             let msg = self.msgs[i];
-            if elements[msg.element as usize] != Some(i)
-                || (cur_time.wrapping_sub(msg.time) as i64).abs() > 5_000_000
             // i32 abs() allows for early msgs
-            {
+            let expired = (cur_time.wrapping_sub(msg.time) as i64).abs() > 5_000_000;
+            if (elements[msg.element as usize] != Some(i) && !kept) || expired {
+                if expired {
+                    self.msgs_expired += 1;
+                }
                 del += 1;
             } else if del > 0 {
                 self.msgs.swap(i - del, i);
@@ -201,33 +1011,444 @@ impl<R: Receiver, C: Controller> Renderer<R, C> {
             self.msgs.truncate(self.msgs.len() - del)
         }
 
+        if self.render_pending {
+            if let Err(e) = self.ctl.render() {
+                self.render_failures += 1;
+                return Err(e);
+            }
+            self.render_pending = false;
+            self.render_failures = 0;
+        }
         Ok(())
     }
+    /// Returns how long a frame loop should wait before retrying after `update_leds` returned `err`,
+    /// or `None` if the error should end the loop.
+    fn retry_delay(&self, err: &Error) -> Option<Duration> {
+        if !matches!(err, Error::Controller(_)) {
+            return None;
+        }
+        let retry = &self.render_retry;
+        if retry
+            .max_failures
+            .is_some_and(|max| self.render_failures >= max)
+        {
+            return None;
+        }
+        let doublings = self.render_failures.saturating_sub(1).min(31);
+        let delay = retry
+            .backoff
+            .checked_mul(1 << doublings)
+            .map_or(retry.max_backoff, |d| d.min(retry.max_backoff));
+        if self.verbose >= 1 {
+            eprintln!(
+                "Failed to render ({} in a row), retrying in {:?}: {:?}",
+                self.render_failures, delay, err
+            );
+        }
+        Some(delay)
+    }
+    /// Renders frames at `target_fps` until an error occurs.
+    /// The statistics of each `stats_period` are printed when `verbose` is at least 2.
     pub fn update_leds_loop(&mut self, target_fps: f64) -> Error {
-        let fps_wait = Duration::from_secs_f64(1.0 / target_fps);
-        let start = Instant::now();
-        let mut fps_period = start;
-        let mut period_start = 0;
-        let mut count = 0;
+        let verbose = self.verbose;
+        self.update_leds_loop_with(target_fps, |stats| {
+            if verbose >= 2 {
+                eprintln!("{}", stats);
+            }
+        })
+    }
+    /// Renders frames at `target_fps` until an error occurs,
+    /// calling `on_stats` with the statistics of each `stats_period`.
+    ///
+    /// Frames are scheduled on fixed boundaries from the start of the loop, so the frame
+    /// rate doesn't drift. Frames that overrun their period are handled by `overrun`.
+    /// Frames the controller fails to render are retried as set by `render_retry`.
+    pub fn update_leds_loop_with<F: FnMut(&FrameStats)>(
+        &mut self,
+        target_fps: f64,
+        on_stats: F,
+    ) -> Error {
+        match self.frame_loop(target_fps, on_stats, |_| LoopControl::Continue) {
+            Some(e) => e,
+            None => unreachable!("The frame loop can only be stopped by its control."),
+        }
+    }
+    /// Runs the frame loop, calling `control` before each frame.
+    /// Returns the error that ended the loop, or `None` if it was stopped by `control`.
+    fn frame_loop<F, G>(
+        &mut self,
+        target_fps: f64,
+        mut on_stats: F,
+        mut control: G,
+    ) -> Option<Error>
+    where
+        F: FnMut(&FrameStats),
+        G: FnMut(&mut Self) -> LoopControl,
+    {
+        let mut schedule = FrameSchedule::new(target_fps, self.msgs_applied, self.msgs_expired);
         loop {
-            let now = Instant::now();
-            sleep((start + (fps_wait * count)).saturating_duration_since(now));
+            sleep(schedule.deadline.saturating_duration_since(Instant::now()));
+            match control(self) {
+                LoopControl::Continue => (),
+                LoopControl::Restart => schedule.restart(),
+                LoopControl::Stop => return None,
+                LoopControl::Fail(e) => return Some(e),
+            }
+            let start = Instant::now();
             if let Err(e) = self.update_leds() {
-                return e;
-            }
-            if self.verbose >= 2 {
-                let since = now.duration_since(fps_period);
-                if since > Duration::from_secs(5) {
-                    eprintln!(
-                        "FPS: {}, Lifetime FPS : {}",
-                        (count - period_start) as f32 / since.as_secs_f32(),
-                        count as f32 / now.duration_since(start).as_secs_f32()
-                    );
-                    period_start = count;
-                    fps_period = now;
+                match self.retry_delay(&e) {
+                    Some(delay) => {
+                        sleep(delay);
+                        schedule.restart();
+                        continue;
+                    }
+                    None => return Some(e),
                 }
             }
-            count += 1;
+            if self.frame_done(&mut schedule, start) {
+                on_stats(&self.stats);
+            }
+        }
+    }
+    /// Records a frame of a frame loop that started at `start`.
+    /// Returns `true` if the statistics were updated.
+    fn frame_done(&mut self, schedule: &mut FrameSchedule, start: Instant) -> bool {
+        let stats = schedule.frame_done(
+            start,
+            Instant::now(),
+            self.overrun,
+            self.stats_period,
+            self.msgs_applied,
+            self.msgs_expired,
+        );
+        match stats {
+            Some(stats) => {
+                self.stats = stats;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Adapts an [`AsyncReceiver`] so that it can be used by a [`Renderer`].
+///
+/// Batches of messages received by the renderer's async loop are queued until its next frame.
+/// See [`Renderer::update_leds_async_loop`].
+/// The blocking frame loops and [`Renderer::spawn`] can also be used, in which case
+/// the inner receiver is polled directly.
+///
+/// [`AsyncReceiver`]: ../trait.AsyncReceiver.html
+/// [`Renderer`]: ./struct.Renderer.html
+/// [`Renderer::update_leds_async_loop`]: ./struct.Renderer.html#method.update_leds_async_loop
+/// [`Renderer::spawn`]: ./struct.Renderer.html#method.spawn
+pub struct AsyncQueue<A: AsyncReceiver> {
+    inner: A,
+    queue: VecDeque<Vec<LedMsg>>,
+}
+impl<A: AsyncReceiver> AsyncQueue<A> {
+    pub fn new(inner: A) -> Self {
+        AsyncQueue {
+            inner,
+            queue: VecDeque::new(),
         }
     }
+    #[inline]
+    pub fn get_ref(&self) -> &A {
+        &self.inner
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+}
+impl<A: AsyncReceiver> Receiver for AsyncQueue<A> {
+    #[inline]
+    fn cur_time(&self) -> u64 {
+        self.inner.cur_time()
+    }
+    /// Returns the oldest queued batch, or waits up to `timeout` for the inner receiver
+    /// if there is none.
+    /// A zero timeout only polls the inner receiver once, which is safe from within the
+    /// renderer's async loop. Longer timeouts must not be used from within an async runtime.
+    fn recv_to(&mut self, timeout: Duration) -> Result<Vec<LedMsg>, Error> {
+        if let Some(msgs) = self.queue.pop_front() {
+            return Ok(msgs);
+        }
+        let res = if timeout == Duration::from_secs(0) {
+            self.inner.recv().now_or_never()
+        } else {
+            rt::block_on(rt::timeout(timeout, self.inner.recv())).ok()
+        };
+        res.unwrap_or_else(|| Err(Error::Timeout("AsyncQueue: recv timeout".to_string())))
+    }
+    /// Returns the oldest queued batch, blocking on the inner receiver if there is none.
+    /// This must not be called from within an async runtime.
+    fn recv(&mut self) -> Result<Vec<LedMsg>, Error> {
+        match self.queue.pop_front() {
+            Some(msgs) => Ok(msgs),
+            None => rt::block_on(self.inner.recv()),
+        }
+    }
+    #[inline]
+    fn set_presentation_delay(&mut self, delay: Duration) {
+        self.inner.set_presentation_delay(delay);
+    }
+}
+
+impl<A: AsyncReceiver, C: Controller> Renderer<AsyncQueue<A>, C> {
+    /// Creates a renderer for an asynchronous receiver.
+    pub fn new_async(recv: A, ctl: C) -> Self {
+        Renderer::new(AsyncQueue::new(recv), ctl)
+    }
+    /// The async version of [`update_leds_loop`](#method.update_leds_loop).
+    /// Messages are received while waiting for each frame, without blocking the executor.
+    pub async fn update_leds_async_loop(&mut self, target_fps: f64) -> Error {
+        let verbose = self.verbose;
+        self.update_leds_async_loop_with(target_fps, |stats| {
+            if verbose >= 2 {
+                eprintln!("{}", stats);
+            }
+        })
+        .await
+    }
+    /// The async version of [`update_leds_loop_with`](#method.update_leds_loop_with).
+    pub async fn update_leds_async_loop_with<F: FnMut(&FrameStats)>(
+        &mut self,
+        target_fps: f64,
+        mut on_stats: F,
+    ) -> Error {
+        let mut schedule = FrameSchedule::new(target_fps, self.msgs_applied, self.msgs_expired);
+        loop {
+            let wait = schedule.deadline.saturating_duration_since(Instant::now());
+            if wait > Duration::from_secs(0) {
+                let timer = Box::pin(rt::sleep(wait));
+                match select(self.recv.inner.recv(), timer).await {
+                    Either::Left((Ok(msgs), _)) => self.recv.queue.push_back(msgs),
+                    Either::Left((Err(e), _)) => return e,
+                    Either::Right(_) => (),
+                }
+                continue;
+            }
+            let start = Instant::now();
+            if let Err(e) = self.update_leds() {
+                match self.retry_delay(&e) {
+                    Some(delay) => {
+                        rt::sleep(delay).await;
+                        schedule.restart();
+                        continue;
+                    }
+                    None => return e,
+                }
+            }
+            if self.frame_done(&mut schedule, start) {
+                on_stats(&self.stats);
+            }
+        }
+    }
+}
+
+impl<R, C> Renderer<R, C>
+where
+    R: Receiver + Send + 'static,
+    C: Controller + Send + 'static,
+{
+    /// Runs the frame loop at `target_fps` on a new thread.
+    /// The returned handle controls the renderer while it runs and returns it when stopped.
+    pub fn spawn(mut self, target_fps: f64) -> Result<RendererHandle<Self>, Error> {
+        RendererHandle::start(
+            move |ctl, stats| match self.run_controlled(target_fps, ctl, stats) {
+                Some(e) => Err(e),
+                None => Ok(self),
+            },
+        )
+    }
+}
+impl<R: Receiver, C: Controller> Renderer<R, C> {
+    /// Runs the frame loop at `target_fps` on a new thread, with the renderer created by `make`
+    /// on that thread.
+    /// This is for receivers and controllers that can't be moved between threads,
+    /// such as LED drivers, so the renderer isn't returned when it is stopped.
+    /// An error from `make` stops the thread and is returned by the handle.
+    pub fn spawn_with<F>(target_fps: f64, make: F) -> Result<RendererHandle<()>, Error>
+    where
+        F: FnOnce() -> Result<Self, Error> + Send + 'static,
+    {
+        RendererHandle::start(move |ctl, stats| {
+            let mut renderer = make()?;
+            match renderer.run_controlled(target_fps, ctl, stats) {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        })
+    }
+    /// Runs the frame loop, applying the requests from a `RendererHandle` before each frame.
+    /// Returns `None` if the handle stopped the renderer.
+    fn run_controlled(
+        &mut self,
+        target_fps: f64,
+        ctl_recv: mpsc::Receiver<Control>,
+        shared: Arc<Mutex<FrameStats>>,
+    ) -> Option<Error> {
+        let verbose = self.verbose;
+        let on_stats = |s: &FrameStats| {
+            if verbose >= 2 {
+                eprintln!("{}", s);
+            }
+            *shared.lock().unwrap() = s.clone();
+        };
+        let mut paused = false;
+        self.frame_loop(target_fps, on_stats, |renderer| {
+            let mut ret = LoopControl::Continue;
+            loop {
+                match ctl_recv.try_recv() {
+                    Ok(Control::Pause) => paused = true,
+                    Ok(Control::Resume) => {
+                        if paused {
+                            ret = LoopControl::Restart;
+                        }
+                        paused = false;
+                    }
+                    Ok(Control::ColorMap(map)) => renderer.color_map = *map,
+                    Ok(Control::Dimmer(v)) => renderer.dimmer = v,
+                    Ok(Control::Layout(layout)) => renderer.layout = layout,
+                    Ok(Control::Stop) | Err(mpsc::TryRecvError::Disconnected) => {
+                        return LoopControl::Stop
+                    }
+                    Err(mpsc::TryRecvError::Empty) if paused => {
+                        // Keep draining the receiver so that senders don't stall while paused.
+                        // The messages are dropped, as they would be stale by the time
+                        // the renderer resumes.
+                        match renderer.recv.recv_to(Duration::from_millis(10)) {
+                            Ok(msgs) => {
+                                if renderer.verbose >= 3 {
+                                    eprintln!("paused, dropping {} msgs", msgs.len());
+                                }
+                            }
+                            Err(Error::Timeout(_)) => (),
+                            Err(e) => return LoopControl::Fail(e),
+                        }
+                    }
+                    Err(mpsc::TryRecvError::Empty) => return ret,
+                }
+            }
+        })
+    }
+}
+
+/// Requests sent from a `RendererHandle` to its renderer's thread.
+enum Control {
+    Pause,
+    Resume,
+    ColorMap(Box<ColorMap>),
+    Dimmer(f32),
+    Layout(Layout),
+    Stop,
+}
+/// What the frame loop does after its control is called.
+enum LoopControl {
+    Continue,
+    /// Continue, but reschedule the frames from now, such as after being paused.
+    Restart,
+    Stop,
+    /// Stop with an error, such as from the receiver while paused.
+    Fail(Error),
+}
+
+/// Controls a [`Renderer`] running on its own thread.
+/// Created by [`Renderer::spawn`], which returns the renderer as `T` when stopped,
+/// or [`Renderer::spawn_with`], which doesn't.
+///
+/// Requests are applied before the next frame.
+/// They fail if the renderer has stopped due to an error, which [`stop`](#method.stop) returns.
+/// Dropping the handle stops the renderer.
+///
+/// [`Renderer`]: ./struct.Renderer.html
+/// [`Renderer::spawn`]: ./struct.Renderer.html#method.spawn
+/// [`Renderer::spawn_with`]: ./struct.Renderer.html#method.spawn_with
+pub struct RendererHandle<T> {
+    ctl: mpsc::Sender<Control>,
+    stats: Arc<Mutex<FrameStats>>,
+    thread: JoinHandle<Result<T, Error>>,
+}
+impl<T: Send + 'static> RendererHandle<T> {
+    /// Runs `f` on a new renderer thread with the receiving end of the handle's requests
+    /// and the shared statistics.
+    fn start<F>(f: F) -> Result<Self, Error>
+    where
+        F: FnOnce(mpsc::Receiver<Control>, Arc<Mutex<FrameStats>>) -> Result<T, Error>
+            + Send
+            + 'static,
+    {
+        let (ctl_send, ctl_recv) = mpsc::channel();
+        let stats = Arc::new(Mutex::new(FrameStats::default()));
+        let shared = stats.clone();
+        let thread = Builder::new()
+            .name("renderer".to_string())
+            .spawn(move || f(ctl_recv, shared))
+            .map_err(|e| Error::Misc(format!("Failed to spawn renderer thread: {}", e)))?;
+        Ok(RendererHandle {
+            ctl: ctl_send,
+            stats,
+            thread,
+        })
+    }
+}
+impl<T> RendererHandle<T> {
+    fn send(&self, ctl: Control) -> Result<(), Error> {
+        self.ctl
+            .send(ctl)
+            .map_err(|_| Error::Unrecoverable("The renderer has stopped.".to_string()))
+    }
+    /// Stops rendering frames, leaving the LEDs showing the last frame.
+    /// Messages received while paused are dropped.
+    pub fn pause(&self) -> Result<(), Error> {
+        self.send(Control::Pause)
+    }
+    pub fn resume(&self) -> Result<(), Error> {
+        self.send(Control::Resume)
+    }
+    pub fn set_color_map(&self, color_map: ColorMap) -> Result<(), Error> {
+        self.send(Control::ColorMap(Box::new(color_map)))
+    }
+    /// Sets the master brightness, from 0 to 1. See `Renderer::dimmer`.
+    pub fn set_dimmer(&self, dimmer: f32) -> Result<(), Error> {
+        self.send(Control::Dimmer(dimmer))
+    }
+    pub fn set_layout(&self, layout: Layout) -> Result<(), Error> {
+        self.send(Control::Layout(layout))
+    }
+    /// Returns the statistics of the frame loop's last completed `stats_period`.
+    pub fn stats(&self) -> FrameStats {
+        self.stats.lock().unwrap().clone()
+    }
+    /// Returns `true` if the renderer's thread has ended, either because of an error
+    /// or because it was stopped.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+    /// Stops the renderer and returns it, or `()` if it was created by `spawn_with`.
+    /// Returns the error that stopped the renderer instead if it stopped before it was asked to.
+    pub fn stop(self) -> Result<T, Error> {
+        // the thread may have already ended
+        let _ = self.ctl.send(Control::Stop);
+        self.join()
+    }
+    /// Waits for the renderer to stop because of an error and returns the error.
+    pub fn wait(self) -> Error {
+        match self.join() {
+            Ok(_) => unreachable!("Only the handle can stop the renderer without an error."),
+            Err(e) => e,
+        }
+    }
+    fn join(self) -> Result<T, Error> {
+        let RendererHandle { ctl, thread, .. } = self;
+        // keep the control channel open so the renderer doesn't see the handle as dropped
+        let ret = thread
+            .join()
+            .map_err(|_| Error::Unrecoverable("The renderer thread panicked.".to_string()));
+        drop(ctl);
+        ret?
+    }
 }
@@ -0,0 +1,186 @@
+//! Easing curves used to shape transitions and time-based commands.
+//!
+//! Each curve has a compact id used in the wire format, see [`Easing::id`].
+//!
+//! [`Easing::id`]: ./enum.Easing.html#method.id
+use crate::Error;
+use std::f32::consts::PI;
+
+/// Maps the linear progress of a transition to its eased progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starts and ends slowly (smoothstep).
+    Smooth,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InSine,
+    OutSine,
+    InOutSine,
+    InExpo,
+    OutExpo,
+    InOutExpo,
+    /// A CSS style cubic Bézier curve from (0, 0) to (1, 1) with the control points
+    /// (x1, y1) and (x2, y2), where each coordinate is scaled from the range [0, 255] to [0, 1].
+    CubicBezier(u8, u8, u8, u8),
+}
+impl Default for Easing {
+    #[inline]
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+impl Easing {
+    /// The maximum number of parameter bytes that can follow an id.
+    pub const MAX_PARAMS: usize = 4;
+
+    /// Returns the eased progress for the linear progress `t`.
+    /// `t` is clamped to the range [0, 1].
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::Smooth => t * t * (3.0 - 2.0 * t),
+            Easing::InQuad => t * t,
+            Easing::OutQuad => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::InOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            }
+            Easing::InCubic => t * t * t,
+            Easing::OutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::InOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - 4.0 * (1.0 - t).powi(3)
+                }
+            }
+            Easing::InSine => 1.0 - (t * PI / 2.0).cos(),
+            Easing::OutSine => (t * PI / 2.0).sin(),
+            Easing::InOutSine => 0.5 - 0.5 * (t * PI).cos(),
+            Easing::InExpo => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Easing::OutExpo => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2f32.powf(-10.0 * t)
+                }
+            }
+            Easing::InOutExpo => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    1.0 - 2f32.powf(-20.0 * t + 10.0) / 2.0
+                }
+            }
+            Easing::CubicBezier(x1, y1, x2, y2) => bezier(
+                t,
+                x1 as f32 / 255.0,
+                y1 as f32 / 255.0,
+                x2 as f32 / 255.0,
+                y2 as f32 / 255.0,
+            ),
+        }
+    }
+    /// Returns the wire id of the curve.
+    /// Ids fit in four bits, with zero being reserved to indicate no easing curve.
+    pub fn id(self) -> u8 {
+        match self {
+            Easing::Linear => 1,
+            Easing::Smooth => 2,
+            Easing::InQuad => 3,
+            Easing::OutQuad => 4,
+            Easing::InOutQuad => 5,
+            Easing::InCubic => 6,
+            Easing::OutCubic => 7,
+            Easing::InOutCubic => 8,
+            Easing::InSine => 9,
+            Easing::OutSine => 10,
+            Easing::InOutSine => 11,
+            Easing::InExpo => 12,
+            Easing::OutExpo => 13,
+            Easing::InOutExpo => 14,
+            Easing::CubicBezier(..) => 15,
+        }
+    }
+    /// Writes the parameters of the curve to `buf` and returns the number of bytes written.
+    pub(crate) fn serialize_params(self, buf: &mut [u8]) -> usize {
+        match self {
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                buf[..4].copy_from_slice(&[x1, y1, x2, y2]);
+                4
+            }
+            _ => 0,
+        }
+    }
+    /// Parses the curve with the given `id`, reading its parameters from the start of `buf`.
+    /// Returns the curve and the number of parameter bytes used.
+    pub(crate) fn deserialize(id: u8, buf: &[u8]) -> Result<(Self, usize), Error> {
+        let ret = match id {
+            1 => Easing::Linear,
+            2 => Easing::Smooth,
+            3 => Easing::InQuad,
+            4 => Easing::OutQuad,
+            5 => Easing::InOutQuad,
+            6 => Easing::InCubic,
+            7 => Easing::OutCubic,
+            8 => Easing::InOutCubic,
+            9 => Easing::InSine,
+            10 => Easing::OutSine,
+            11 => Easing::InOutSine,
+            12 => Easing::InExpo,
+            13 => Easing::OutExpo,
+            14 => Easing::InOutExpo,
+            15 => {
+                let p = buf.get(..4).ok_or_else(|| {
+                    Error::BadInput("Cubic bezier parameters were truncated.".to_string())
+                })?;
+                return Ok((Easing::CubicBezier(p[0], p[1], p[2], p[3]), 4));
+            }
+            v => {
+                return Err(Error::BadInput(format!(
+                    "Unknown easing curve was given: {:#04X}",
+                    v
+                )))
+            }
+        };
+        Ok((ret, 0))
+    }
+}
+
+/// Evaluates the y value of the cubic Bézier curve at the given `x`.
+fn bezier(x: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    // coordinate of the curve at `s` for control points `a` and `b`
+    let curve = |s: f32, a: f32, b: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * a + 3.0 * inv * s * s * b + s * s * s
+    };
+    // x is monotonic in s because x1 and x2 are in [0, 1], so bisect for s
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..20 {
+        let mid = (lo + hi) / 2.0;
+        if curve(mid, x1, x2) < x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    curve((lo + hi) / 2.0, y1, y2)
+}
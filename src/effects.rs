@@ -0,0 +1,155 @@
+//! Evaluation of the commands that fill their element.
+//!
+//! Every effect is a pure function of the time elapsed since the message's
+//! time and the position of the LED within the element.
+//! Because receivers share a synchronized clock, the same message renders
+//! identically on every receiver.
+//! Effects that need randomness draw it from a [`Noise`] seeded from the message.
+//!
+//! [`Noise`]: ../noise/struct.Noise.html
+use crate::color::{Color, ColorF};
+use crate::easing::Easing;
+use crate::noise::Noise;
+use crate::Command;
+
+/// Returns how much of the LED at `pos` is covered by the region [`start`, `end`),
+/// where both are measured in LEDs.
+/// Partially covered LEDs are drawn with proportional intensity so that region edges
+/// move smoothly rather than jumping a whole LED at a time.
+#[inline]
+pub fn coverage(pos: usize, start: f32, end: f32) -> f32 {
+    let pos = pos as f32;
+    (end.min(pos + 1.0) - start.max(pos)).max(0.0)
+}
+
+/// Splits `elapsed` microseconds into the number of completed periods and
+/// the fraction of the current period.
+/// A `period` of zero is treated as an infinitely long period that has just begun.
+#[inline]
+fn phase(elapsed: u64, period: u16) -> (u64, f32) {
+    let period = period as u64 * 1000;
+    if period == 0 {
+        return (0, 0.0);
+    }
+    (elapsed / period, (elapsed % period) as f32 / period as f32)
+}
+
+/// Evaluates the color of LED `pos` of an element that is `len` LEDs long,
+/// `elapsed` microseconds after the time of the message.
+/// `noise` should be created from the message using [`Noise::from_msg`].
+/// `easing` is the message's easing curve, which reshapes the progress of time-based commands.
+///
+/// Returns `None` if `cmd` is not rendered per element, such as `FlatStack` and global commands.
+///
+/// [`Noise::from_msg`]: ../noise/struct.Noise.html#method.from_msg
+pub fn eval(
+    cmd: Command,
+    color: ColorF,
+    noise: &Noise,
+    easing: Option<Easing>,
+    elapsed: u64,
+    pos: usize,
+    len: usize,
+) -> Option<ColorF> {
+    let len_f = len.max(1) as f32;
+    let pos_f = pos as f32;
+    // eases `t` with the message's curve or `default` if it has none
+    let ease = |t: f32, default: Easing| easing.unwrap_or(default).apply(t);
+    let ret = match cmd {
+        Command::Null => ColorF::BLACK,
+        Command::Flat(v) => color * (v as f32 / 255.0),
+        Command::PulseLinear(v) | Command::PulseQuadratic(v) => {
+            let default = match cmd {
+                Command::PulseLinear(_) => Easing::Linear,
+                _ => Easing::OutQuad,
+            };
+            let duration = v as f32 * 10_000.0;
+            let t = if duration == 0.0 {
+                1.0
+            } else {
+                elapsed as f32 / duration
+            };
+            color * (1.0 - ease(t, default))
+        }
+        Command::Wipe { period } => {
+            let progress = if period == 0 {
+                1.0
+            } else {
+                ease(elapsed as f32 / (period as f32 * 1000.0), Easing::Linear)
+            };
+            color * coverage(pos, 0.0, progress * len_f)
+        }
+        Command::Chase { period, width } => {
+            let (_, phase) = phase(elapsed, period);
+            let phase = ease(phase, Easing::Linear);
+            let width = (width as f32 / 255.0 * len_f).max(1.0);
+            let head = phase * len_f;
+            // the segment may wrap around either end of the element
+            let covered: f32 = [-len_f, 0.0, len_f]
+                .iter()
+                .map(|shift| coverage(pos, head - width + shift, head + shift))
+                .sum();
+            color * covered.min(1.0)
+        }
+        Command::Strobe { period, duty } => {
+            let (_, phase) = phase(elapsed, period);
+            if phase < duty as f32 / 255.0 {
+                color
+            } else {
+                ColorF::BLACK
+            }
+        }
+        Command::Rainbow { period, spread } => {
+            let (_, phase) = phase(elapsed, period);
+            let phase = ease(phase, Easing::Linear);
+            let hue = phase + spread as f32 * pos_f / len_f;
+            ColorF::from(Color::wheel(hue)) * color.brightness()
+        }
+        Command::Sparkle {
+            period, density, ..
+        } => {
+            // Offset each LED so that sparkles don't all start together.
+            let offset = noise.value(pos, u64::MAX) % (period as u64 * 1000).max(1);
+            let (slot, phase) = phase(elapsed + offset, period);
+            let mut rng = noise.rng(pos, slot);
+            if (rng.next_u64() & 0xFF) < density as u64 {
+                let fade = 1.0 - ease(phase, Easing::OutQuad);
+                color * (fade * (0.25 + 0.75 * rng.next_f32()))
+            } else {
+                ColorF::BLACK
+            }
+        }
+        Command::Fire {
+            period, intensity, ..
+        } => {
+            let (slot, phase) = phase(elapsed, period);
+            let flicker = noise.smooth(pos, slot, phase);
+            let reach = intensity as f32 / 255.0;
+            let height = if reach > 0.0 {
+                (1.0 - pos_f / len_f / reach).max(0.0)
+            } else {
+                0.0
+            };
+            let heat = height * (0.5 + 0.5 * flicker);
+            // cooler parts of the flame lose their green and blue first
+            ColorF {
+                red: color.red * heat,
+                green: color.green * heat * heat,
+                blue: color.blue * heat * heat * heat,
+                alpha: color.alpha,
+            }
+        }
+        Command::Breathe { period } => {
+            let (_, phase) = phase(elapsed, period);
+            // rise for the first half of the period and fall for the second
+            let tri = 1.0 - (2.0 * phase - 1.0).abs();
+            color * ease(tri, Easing::InOutSine)
+        }
+        Command::FlatStack(_)
+        | Command::Blackout
+        | Command::Dimmer(_)
+        | Command::Freeze
+        | Command::Resume => return None,
+    };
+    Some(ret)
+}
@@ -0,0 +1,132 @@
+//! Sends each batch of messages to several receivers at once.
+//!
+//! A [`MultiSender`] has its own clock. Message times given to it are converted into the
+//! clock of each link before sending, so the receivers show them at the same moment
+//! even though each link is synced to a different receiver.
+//!
+//! [`MultiSender`]: ./struct.MultiSender.html
+use crate::{rt, AsyncSender, Error, LedMsg, Sender};
+use futures::future::{join_all, BoxFuture};
+use std::time::{Duration, Instant};
+
+/// An [`AsyncSender`](../trait.AsyncSender.html) that sends to every one of its links in parallel.
+/// It can also be used as a blocking [`Sender`](../trait.Sender.html).
+pub struct MultiSender {
+    start: Instant,
+    links: Vec<Box<dyn AsyncSender + Send>>,
+}
+impl Default for MultiSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MultiSender {
+    pub fn new() -> Self {
+        MultiSender {
+            start: Instant::now(),
+            links: Vec::new(),
+        }
+    }
+    /// Adds a link and returns its index.
+    pub fn add<S: AsyncSender + Send + 'static>(&mut self, link: S) -> usize {
+        self.links.push(Box::new(link));
+        self.links.len() - 1
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+    /// Sends `msgs` to every link, returning the result of each link in the order they were added.
+    /// A link failing doesn't stop the batch from being sent to the others.
+    ///
+    /// If `is_time_offset` is `false`, the message times are in this sender's clock,
+    /// see [`get_time`](../trait.AsyncSender.html#tymethod.get_time).
+    pub async fn send_all(
+        &mut self,
+        msgs: &[LedMsg],
+        is_time_offset: bool,
+    ) -> Vec<Result<(), Error>> {
+        let cur_time = AsyncSender::get_time(self);
+        let mut batches: Vec<Vec<LedMsg>> = self
+            .links
+            .iter()
+            .map(|link| {
+                let mut batch = msgs.to_vec();
+                if !is_time_offset {
+                    let offset = link.get_time().wrapping_sub(cur_time);
+                    for msg in batch.iter_mut() {
+                        msg.time = msg.time.wrapping_add(offset);
+                    }
+                }
+                batch
+            })
+            .collect();
+        let sends = self
+            .links
+            .iter_mut()
+            .zip(batches.iter_mut())
+            .map(|(link, batch)| link.send(batch, is_time_offset));
+        join_all(sends).await
+    }
+}
+/// Collects the failures of `send_all` into a single error.
+fn summarize(results: Vec<Result<(), Error>>) -> Result<(), Error> {
+    let total = results.len();
+    let failed: Vec<String> = results
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, res)| res.err().map(|e| format!("link {}: {:?}", i, e)))
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Misc(format!(
+            "MultiSender: {} of {} links failed: {}",
+            failed.len(),
+            total,
+            failed.join(", ")
+        )))
+    }
+}
+impl AsyncSender for MultiSender {
+    /// Sends `msgs` to every link.
+    /// If any link fails, the batch is still sent to the others and an error naming
+    /// the failed links is returned. Use [`send_all`](#method.send_all) for the result of each link.
+    fn send<'a>(
+        &'a mut self,
+        msgs: &'a mut [LedMsg],
+        is_time_offset: bool,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { summarize(self.send_all(msgs, is_time_offset).await) })
+    }
+    #[inline]
+    fn get_time(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+    /// Returns the longest presentation delay of the links that can report one,
+    /// so that messages sent far enough ahead are shown in time by every receiver.
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        self.links
+            .iter()
+            .filter_map(|link| link.presentation_delay().ok())
+            .max()
+            .ok_or_else(|| Error::Misc("MultiSender: no link reported a delay.".to_string()))
+    }
+}
+impl Sender for MultiSender {
+    fn send(&mut self, msgs: &mut [LedMsg], is_time_offset: bool) -> Result<(), Error> {
+        rt::block_on(AsyncSender::send(self, msgs, is_time_offset))
+    }
+    #[inline]
+    fn get_time(&self) -> u64 {
+        AsyncSender::get_time(self)
+    }
+    #[inline]
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        AsyncSender::presentation_delay(self)
+    }
+}
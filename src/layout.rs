@@ -0,0 +1,221 @@
+//! Maps elements onto the LEDs of the strip.
+//!
+//! The strip is divided into named [`Segment`]s and every element is assigned to one.
+//! `FlatStack` elements in the same segment stack on each other, independently of
+//! other segments, so one strip can show several bar graphs.
+//! Other commands fill their element's whole segment.
+//! A segment's [`Symmetry`] can mirror or repeat everything drawn into it.
+//! On controllers with several outputs, the LEDs of the outputs are numbered one after
+//! another, so a segment can cover one output or span several.
+//!
+//! [`Segment`]: ./struct.Segment.html
+//! [`Symmetry`]: ./enum.Symmetry.html
+use crate::Error;
+use std::ops::Range;
+
+/// The direction positions within a segment increase in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Positions increase with the LED index.
+    Forward,
+    /// Positions increase towards the start of the strip.
+    Reverse,
+}
+impl Default for Direction {
+    #[inline]
+    fn default() -> Self {
+        Direction::Forward
+    }
+}
+
+/// Repeats or mirrors what is rendered into a segment.
+///
+/// Commands are rendered over a number of positions that depends on the symmetry,
+/// and each position is then drawn on one or more of the segment's LEDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Each position is drawn on one LED.
+    None,
+    /// Positions grow outward from the center of the segment to both of its ends.
+    CenterOut,
+    /// Positions grow inward from both ends of the segment to its center.
+    EdgesIn,
+    /// The segment is split into `n` equal parts that each show all of the positions.
+    Repeat(u8),
+    /// Like `Repeat`, but every other part is reversed, so neighboring parts mirror each other.
+    /// `Mirror(2)` mirrors the two halves of the segment and is the same as `EdgesIn`.
+    Mirror(u8),
+}
+impl Default for Symmetry {
+    #[inline]
+    fn default() -> Self {
+        Symmetry::None
+    }
+}
+
+/// A named run of LEDs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub name: String,
+    /// The index of the first LED of the segment.
+    pub start: usize,
+    /// The number of LEDs in the segment.
+    /// Segments running past the end of the strip are truncated.
+    pub len: usize,
+    pub direction: Direction,
+    pub symmetry: Symmetry,
+}
+impl Segment {
+    pub fn new<S: Into<String>>(name: S, start: usize, len: usize, direction: Direction) -> Self {
+        Segment {
+            name: name.into(),
+            start,
+            len,
+            direction,
+            symmetry: Symmetry::None,
+        }
+    }
+    #[inline]
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+    /// Returns the LEDs covered by the segment on a strip of `leds` LEDs.
+    #[inline]
+    pub fn range(&self, leds: usize) -> Range<usize> {
+        self.start.min(leds)..self.start.saturating_add(self.len).min(leds)
+    }
+    /// Returns the number of positions commands are rendered over on a strip of `leds` LEDs.
+    pub fn positions(&self, leds: usize) -> usize {
+        let len = self.range(leds).len();
+        match self.symmetry {
+            Symmetry::None => len,
+            Symmetry::CenterOut | Symmetry::EdgesIn => len.div_ceil(2),
+            Symmetry::Repeat(n) | Symmetry::Mirror(n) => {
+                let n = n.max(1) as usize;
+                len.div_ceil(n)
+            }
+        }
+    }
+    /// Calls `f` with every LED that position `pos` of the segment is drawn on,
+    /// for a strip of `leds` LEDs.
+    /// `pos` must be less than [`positions`](#method.positions).
+    pub fn for_each_led<F: FnMut(usize)>(&self, pos: usize, leds: usize, mut f: F) {
+        let range = self.range(leds);
+        let len = range.len();
+        let mut draw = |offset: usize| {
+            if offset < len {
+                f(match self.direction {
+                    Direction::Forward => range.start + offset,
+                    Direction::Reverse => range.end - 1 - offset,
+                })
+            }
+        };
+        match self.symmetry {
+            Symmetry::None => draw(pos),
+            Symmetry::CenterOut => {
+                let (left, right) = ((len - 1) / 2 - pos, len / 2 + pos);
+                draw(left);
+                if left != right {
+                    draw(right);
+                }
+            }
+            Symmetry::EdgesIn => {
+                let (left, right) = (pos, len - 1 - pos);
+                draw(left);
+                if left != right {
+                    draw(right);
+                }
+            }
+            Symmetry::Repeat(n) | Symmetry::Mirror(n) => {
+                let width = self.positions(leds);
+                let mirror = matches!(self.symmetry, Symmetry::Mirror(_));
+                for k in 0..n.max(1) as usize {
+                    let start = k * width;
+                    if mirror && k % 2 == 1 {
+                        // mirrored parts are drawn from their end, so a part cut short by
+                        // the end of the segment still meets its neighbor
+                        let end = (start + width).min(len);
+                        if pos < end.saturating_sub(start) {
+                            draw(end - 1 - pos);
+                        }
+                    } else {
+                        draw(start + pos);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Assigns elements to the segments of the strip.
+///
+/// By default, there is a single segment named `"strip"` that covers the whole strip,
+/// and all elements are assigned to it.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    segments: Vec<Segment>,
+    elements: Vec<usize>,
+}
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            segments: vec![Segment::new(
+                Layout::DEFAULT_SEGMENT,
+                0,
+                usize::MAX,
+                Direction::Forward,
+            )],
+            elements: vec![0; 256],
+        }
+    }
+}
+impl Layout {
+    /// The name of the segment all elements are assigned to by default.
+    pub const DEFAULT_SEGMENT: &'static str = "strip";
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a segment to the layout, replacing any existing segment with the same name.
+    /// Returns the index of the segment.
+    pub fn add_segment(&mut self, segment: Segment) -> usize {
+        match self.segments.iter().position(|s| s.name == segment.name) {
+            Some(i) => {
+                self.segments[i] = segment;
+                i
+            }
+            None => {
+                self.segments.push(segment);
+                self.segments.len() - 1
+            }
+        }
+    }
+    /// Assigns `elements` to the segment named `segment`.
+    pub fn assign<I: IntoIterator<Item = u8>>(
+        &mut self,
+        elements: I,
+        segment: &str,
+    ) -> Result<(), Error> {
+        let idx = self
+            .segments
+            .iter()
+            .position(|s| s.name == segment)
+            .ok_or_else(|| Error::BadInput(format!("Unknown segment: {}", segment)))?;
+        for e in elements {
+            self.elements[e as usize] = idx;
+        }
+        Ok(())
+    }
+    #[inline]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+    pub fn segment(&self, name: &str) -> Option<&Segment> {
+        self.segments.iter().find(|s| s.name == name)
+    }
+    /// Returns the index of the segment `element` is assigned to.
+    #[inline]
+    pub fn segment_of(&self, element: u8) -> usize {
+        self.elements[element as usize]
+    }
+}
@@ -7,9 +7,24 @@
 //! ## Current implemented control mechanisms
 //! - Bluetooth Low Energy with **bluetooth** feature.
 //! - RFM69HCW packet radio with **ham** feature.
+//!
+//! ## Async runtimes
+//! The async transports and the renderer's async loop run on
+//! async-std with the **async-std** feature (default) or tokio with the **tokio** feature.
 
 pub mod color;
 pub mod controller;
+pub mod easing;
+pub mod effects;
+pub mod fanout;
+pub mod layout;
+pub mod merge;
+pub mod noise;
+pub mod pixel;
+pub mod power;
+mod rt;
+pub mod safety;
+pub mod stats;
 
 #[cfg(feature = "bluetooth")]
 pub mod bluetooth;
@@ -18,8 +33,15 @@ pub mod bluetooth;
 pub mod tests;
 
 // use ham::{PacketReceiver, PacketSender};
+use color::BlendMode;
+use easing::Easing;
+use futures::channel::mpsc as async_mpsc;
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt};
+use futures::SinkExt;
 use std::convert::TryFrom;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
 use std::time::{Duration, Instant};
 
 /// `LedMsg` is the message format used control LEDs.
@@ -37,6 +59,19 @@ pub struct LedMsg {
     pub color: u8,
     /// Controls what the LED does.
     pub cmd: Command,
+    /// Controls how the element is composited with the elements below it.
+    /// Each element is a layer, with higher elements drawn on top of lower ones.
+    pub blend_mode: BlendMode,
+    /// The duration in milliseconds of the crossfade from the element's previous state to this message.
+    /// If `None` the receiver's default transition is used.
+    pub transition: Option<u16>,
+    /// Shapes the crossfade to this message and the progress of time-based commands.
+    /// If `None` the receiver's default transition easing and the command's own shape are used.
+    pub easing: Option<Easing>,
+    /// Identifies the sender of the message, such as one of several consoles sharing a rig.
+    /// The receiver uses it to arbitrate between senders addressing the same element,
+    /// see [`MergePolicy`](./controller/enum.MergePolicy.html).
+    pub source: u8,
 }
 impl Default for LedMsg {
     #[inline]
@@ -46,17 +81,173 @@ impl Default for LedMsg {
             element: 0,
             color: 0,
             cmd: Command::Null,
+            blend_mode: BlendMode::default(),
+            transition: None,
+            easing: None,
+            source: 0,
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Command {
+    /// Leaves the element dark.
     Null,
+    /// Fills the element at a brightness of `v`/255.
     Flat(u8),
+    /// Flashes the element and fades it out linearly over `v` hundredths of a second.
     PulseLinear(u8),
+    /// Flashes the element and fades it out quadratically over `v` hundredths of a second.
     PulseQuadratic(u8),
+    /// Fills `v + 1` 256ths of the element's segment, starting where the previous
+    /// `FlatStack` element in the segment ended.
+    /// See [`Layout`](./layout/struct.Layout.html).
     FlatStack(u8),
+    /// Fills the element from its first LED to its last over `period` milliseconds and then holds.
+    Wipe { period: u16 },
+    /// A lit segment, `width`/255 of the element long, travels along the element once every `period` milliseconds.
+    Chase { period: u16, width: u8 },
+    /// Flashes the element once every `period` milliseconds, staying on for `duty`/255 of the period.
+    Strobe { period: u16, duty: u8 },
+    /// Cycles through the color wheel once every `period` milliseconds.
+    /// `spread` is the number of times the wheel is repeated across the element.
+    Rainbow { period: u16, spread: u8 },
+    /// Randomly lights LEDs, each lit LED fading out over `period` milliseconds.
+    /// Roughly `density`/255 of the LEDs are lit at any time.
+    /// `seed` selects the random pattern, see [`Noise`](./noise/struct.Noise.html).
+    Sparkle { period: u16, density: u8, seed: u8 },
+    /// Flickering flame that changes every `period` milliseconds.
+    /// `intensity` controls how much of the element is ablaze.
+    /// `seed` selects the random pattern, see [`Noise`](./noise/struct.Noise.html).
+    Fire {
+        period: u16,
+        intensity: u8,
+        seed: u8,
+    },
+    /// Smoothly fades the element in and out once every `period` milliseconds.
+    Breathe { period: u16 },
+    /// Turns off every LED of the receiver until `Resume` is received.
+    /// This is a global command, see [`is_global`](#method.is_global).
+    Blackout,
+    /// Sets the master brightness of the receiver to `v`/255.
+    /// This is a global command, see [`is_global`](#method.is_global).
+    Dimmer(u8),
+    /// Holds the current frame until `Resume` is received.
+    /// This is a global command, see [`is_global`](#method.is_global).
+    Freeze,
+    /// Ends a `Blackout` or `Freeze`. The master brightness is unchanged.
+    /// This is a global command, see [`is_global`](#method.is_global).
+    Resume,
+}
+impl Command {
+    /// Returns `true` if the command controls the receiver as a whole rather than an element.
+    /// The element and color of messages with global commands are ignored.
+    #[inline]
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self,
+            Command::Blackout | Command::Dimmer(_) | Command::Freeze | Command::Resume
+        )
+    }
+    /// The value of the command bits in the flags byte for extended commands.
+    /// Extended commands are followed by a byte identifying the command and then its parameters.
+    const EXTENDED: u8 = 0x07;
+    /// The maximum length of an extended command: command id + period + parameters.
+    const MAX_EXT_LEN: usize = 1 + 2 + 2;
+    /// Writes the extended command id and parameters to `buf` and returns the number of bytes written.
+    /// Returns `None` if `self` is not an extended command.
+    fn serialize_ext(&self, buf: &mut [u8]) -> Option<usize> {
+        let (id, period, params, len) = match *self {
+            Command::Wipe { period } => (0x00, period, [0, 0], 0),
+            Command::Chase { period, width } => (0x01, period, [width, 0], 1),
+            Command::Strobe { period, duty } => (0x02, period, [duty, 0], 1),
+            Command::Rainbow { period, spread } => (0x03, period, [spread, 0], 1),
+            Command::Sparkle {
+                period,
+                density,
+                seed,
+            } => (0x04, period, [density, seed], 2),
+            Command::Fire {
+                period,
+                intensity,
+                seed,
+            } => (0x05, period, [intensity, seed], 2),
+            Command::Breathe { period } => (0x06, period, [0, 0], 0),
+            // global commands have no period, so it is sent as zero
+            Command::Blackout => (0x10, 0, [0, 0], 0),
+            Command::Dimmer(v) => (0x11, 0, [v, 0], 1),
+            Command::Freeze => (0x12, 0, [0, 0], 0),
+            Command::Resume => (0x13, 0, [0, 0], 0),
+            _ => return None,
+        };
+        buf[0] = id;
+        buf[1..3].copy_from_slice(&period.to_le_bytes());
+        buf[3..3 + len].copy_from_slice(&params[..len]);
+        Some(3 + len)
+    }
+    /// Parses an extended command from the start of `buf`.
+    /// Returns the command and the number of bytes it used.
+    fn deserialize_ext(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let short = || Error::BadInput("Extended command was truncated.".to_string());
+        let id = *buf.first().ok_or_else(short)?;
+        let period = match buf.get(1..3) {
+            Some(p) => u16::from_le_bytes([p[0], p[1]]),
+            None => return Err(short()),
+        };
+        let param = |n: usize| buf.get(3 + n).copied().ok_or_else(short);
+        let ret = match id {
+            0x00 => (Command::Wipe { period }, 3),
+            0x01 => (
+                Command::Chase {
+                    period,
+                    width: param(0)?,
+                },
+                4,
+            ),
+            0x02 => (
+                Command::Strobe {
+                    period,
+                    duty: param(0)?,
+                },
+                4,
+            ),
+            0x03 => (
+                Command::Rainbow {
+                    period,
+                    spread: param(0)?,
+                },
+                4,
+            ),
+            0x04 => (
+                Command::Sparkle {
+                    period,
+                    density: param(0)?,
+                    seed: param(1)?,
+                },
+                5,
+            ),
+            0x05 => (
+                Command::Fire {
+                    period,
+                    intensity: param(0)?,
+                    seed: param(1)?,
+                },
+                5,
+            ),
+            0x06 => (Command::Breathe { period }, 3),
+            0x10 => (Command::Blackout, 3),
+            0x11 => (Command::Dimmer(param(0)?), 4),
+            0x12 => (Command::Freeze, 3),
+            0x13 => (Command::Resume, 3),
+            v => {
+                return Err(Error::BadInput(format!(
+                    "Unknown extended command was given: {:#04X}",
+                    v
+                )))
+            }
+        };
+        Ok(ret)
+    }
 }
 
 #[derive(Debug)]
@@ -73,6 +264,8 @@ pub enum Error {
     #[cfg(feature = "bluetooth")]
     BtMsg(btutils::messaging::Error),
     NotConnected,
+    /// The controller failed to show the LEDs.
+    Controller(String),
 }
 impl From<ham::Error> for Error {
     fn from(err: ham::Error) -> Self {
@@ -100,7 +293,20 @@ impl From<btutils::messaging::Error> for Error {
 const U32_MAX: u64 = std::u32::MAX as u64;
 
 impl LedMsg {
-    pub const MAX_LEN: usize = 1 + 2 + 4 + 1; // flags + color/elment + time + cmd_value
+    pub const MAX_LEN: usize = 1 + 2 + 4 + 1 + 3 + Easing::MAX_PARAMS + Command::MAX_EXT_LEN; // flags + color/elment + time + source + options + cmd_value
+    /// Flag bit indicating that the message has an options byte following its time offset.
+    /// The lowest three bits of the options byte are the [`BlendMode`] and the highest four
+    /// bits are the id of the [`Easing`] curve, or zero if there is none.
+    /// The transition duration and then the easing curve parameters follow the options byte.
+    ///
+    /// [`BlendMode`]: ./color/enum.BlendMode.html
+    /// [`Easing`]: ./easing/enum.Easing.html
+    const OPTIONS: u8 = 0x20;
+    /// Options bit indicating that the transition duration follows the options byte.
+    const OPT_TRANSITION: u8 = 0x08;
+    /// Flag bit indicating that the message has a source byte following its time offset.
+    /// Messages without it are from source zero.
+    const SOURCE: u8 = 0x01;
     fn deserialize(buf: &[u8], cur_time: u64) -> Result<Vec<LedMsg>, Error> {
         let mut ret = Vec::new();
         if buf.is_empty() {
@@ -157,24 +363,54 @@ impl LedMsg {
                 }
                 _ => unreachable!(),
             };
+            let (source, extra_src) = if buf[i] & LedMsg::SOURCE != 0 {
+                (*buf.get(i + 3 + extra0).ok_or_else(extra_bytes)?, 1)
+            } else {
+                (0, 0)
+            };
+            let extra0 = extra0 + extra_src;
+            let (blend_mode, transition, easing, extra_opt) = if buf[i] & LedMsg::OPTIONS != 0 {
+                let opt = i + 3 + extra0;
+                let opts = *buf.get(opt).ok_or_else(extra_bytes)?;
+                let blend_mode = BlendMode::from_u8(opts & 0x07)?;
+                let mut end = opt + 1;
+                let transition = if opts & LedMsg::OPT_TRANSITION != 0 {
+                    let t = buf.get(end..end + 2).ok_or_else(extra_bytes)?;
+                    end += 2;
+                    Some(u16::from_le_bytes([t[0], t[1]]))
+                } else {
+                    None
+                };
+                let easing = match opts >> 4 {
+                    0 => None,
+                    id => {
+                        let (easing, used) =
+                            Easing::deserialize(id, buf.get(end..).unwrap_or(&[]))?;
+                        end += used;
+                        Some(easing)
+                    }
+                };
+                (blend_mode, transition, easing, end - opt)
+            } else {
+                (BlendMode::default(), None, None, 0)
+            };
+            let val = i + 3 + extra0 + extra_opt;
             let (cmd, extra1) = match (buf[i] >> 2) & 0x07 {
                 0x00 => (Command::Null, 0),
-                0x01 => (
-                    Command::Flat(*buf.get(i + 3 + extra0).ok_or_else(extra_bytes)?),
-                    1,
-                ),
+                0x01 => (Command::Flat(*buf.get(val).ok_or_else(extra_bytes)?), 1),
                 0x02 => (
-                    Command::PulseLinear(*buf.get(i + 3 + extra0).ok_or_else(extra_bytes)?),
+                    Command::PulseLinear(*buf.get(val).ok_or_else(extra_bytes)?),
                     1,
                 ),
                 0x03 => (
-                    Command::PulseQuadratic(*buf.get(i + 3 + extra0).ok_or_else(extra_bytes)?),
+                    Command::PulseQuadratic(*buf.get(val).ok_or_else(extra_bytes)?),
                     1,
                 ),
                 0x04 => (
-                    Command::FlatStack(*buf.get(i + 3 + extra0).ok_or_else(extra_bytes)?),
+                    Command::FlatStack(*buf.get(val).ok_or_else(extra_bytes)?),
                     1,
                 ),
+                Command::EXTENDED => Command::deserialize_ext(buf.get(val..).unwrap_or(&[]))?,
                 v => {
                     return Err(Error::BadInput(format!(
                         "Unknown command was given: {:#04X}",
@@ -188,9 +424,13 @@ impl LedMsg {
                 element: buf[i + 1],
                 color: buf[i + 2],
                 cmd,
+                blend_mode,
+                transition,
+                easing,
+                source,
             };
             ret.push(msg);
-            i += 3 + extra0 + extra1;
+            i += 3 + extra0 + extra_opt + extra1;
         }
         Ok(ret)
     }
@@ -199,7 +439,7 @@ impl LedMsg {
         ret[0..4].copy_from_slice(&cur_time.to_le_bytes()[0..4]);
         let mut i = 4;
         for (j, msg) in msgs.iter().enumerate() {
-            let mut buf = [0u8; 8];
+            let mut buf = [0u8; LedMsg::MAX_LEN];
             let offset = msg.time.wrapping_sub(cur_time) as i64;
             let (flag0, extra0) = if offset == 0 {
                 ((0x0 << 6), 0)
@@ -216,29 +456,62 @@ impl LedMsg {
                 // messages outside the interval are ignored
                 continue;
             };
+            let (flag_src, extra0) = if msg.source != 0 {
+                buf[3 + extra0] = msg.source;
+                (LedMsg::SOURCE, extra0 + 1)
+            } else {
+                (0, extra0)
+            };
+            // options are only sent when they differ from the defaults
+            let opt = 3 + extra0;
+            let (flag_opt, extra_opt) = if msg.blend_mode != BlendMode::default()
+                || msg.transition.is_some()
+                || msg.easing.is_some()
+            {
+                let mut opts = msg.blend_mode as u8;
+                let mut end = opt + 1;
+                if let Some(t) = msg.transition {
+                    opts |= LedMsg::OPT_TRANSITION;
+                    buf[end..end + 2].copy_from_slice(&t.to_le_bytes());
+                    end += 2;
+                }
+                if let Some(easing) = msg.easing {
+                    opts |= easing.id() << 4;
+                    end += easing.serialize_params(&mut buf[end..]);
+                }
+                buf[opt] = opts;
+                (LedMsg::OPTIONS, end - opt)
+            } else {
+                (0, 0)
+            };
+            let val = 3 + extra0 + extra_opt;
             let (flag1, extra1) = match msg.cmd {
                 Command::Null => (0x00 << 2, 0),
                 Command::Flat(v) => {
-                    buf[3 + extra0] = v;
+                    buf[val] = v;
                     (0x01 << 2, 1)
                 }
                 Command::PulseLinear(v) => {
-                    buf[3 + extra0] = v;
+                    buf[val] = v;
                     (0x02 << 2, 1)
                 }
                 Command::PulseQuadratic(v) => {
-                    buf[3 + extra0] = v;
+                    buf[val] = v;
                     (0x03 << 2, 1)
                 }
                 Command::FlatStack(v) => {
-                    buf[3 + extra0] = v;
+                    buf[val] = v;
                     (0x04 << 2, 1)
                 }
+                cmd => {
+                    let extra1 = cmd.serialize_ext(&mut buf[val..]).unwrap();
+                    (Command::EXTENDED << 2, extra1)
+                }
             };
-            let msg_len = extra0 + extra1 + 3;
+            let msg_len = val + extra1;
             if i + msg_len <= ret.len() {
                 // we have enough room in the buffer so append
-                buf[0] = flag0 | flag1;
+                buf[0] = flag0 | flag_src | flag_opt | flag1;
                 buf[1] = msg.element;
                 buf[2] = msg.color;
                 ret[i..i + msg_len].copy_from_slice(&buf[..msg_len]);
@@ -263,6 +536,14 @@ pub trait Receiver {
         self.recv_to(Duration::from_secs(0))
     }
     fn recv(&mut self) -> Result<Vec<LedMsg>, Error>;
+    /// Called by the [`Renderer`] when its presentation delay changes,
+    /// so that receivers that are able to can report it to senders.
+    /// See [`Sender::presentation_delay`].
+    ///
+    /// [`Renderer`]: ./controller/struct.Renderer.html
+    /// [`Sender::presentation_delay`]: ./trait.Sender.html#method.presentation_delay
+    #[inline]
+    fn set_presentation_delay(&mut self, _delay: Duration) {}
     fn try_iter(&mut self) -> TryIter<'_, Self>
     where
         Self: Sized,
@@ -270,6 +551,33 @@ pub trait Receiver {
         TryIter { recv: self }
     }
 }
+/// The asynchronous counterpart of [`Receiver`](./trait.Receiver.html).
+///
+/// Rather than blocking, receiving resolves once a batch of messages arrives,
+/// so receivers can be used from within an async runtime.
+pub trait AsyncReceiver {
+    fn cur_time(&self) -> u64;
+    /// Receives the next batch of messages.
+    fn recv(&mut self) -> BoxFuture<'_, Result<Vec<LedMsg>, Error>>;
+    /// See [`Receiver::set_presentation_delay`](./trait.Receiver.html#method.set_presentation_delay).
+    #[inline]
+    fn set_presentation_delay(&mut self, _delay: Duration) {}
+    /// Returns a `Stream` of the batches of messages received.
+    /// The stream ends after the first error.
+    fn stream(&mut self) -> BoxStream<'_, Result<Vec<LedMsg>, Error>>
+    where
+        Self: Send + Sized,
+    {
+        futures::stream::unfold(Some(self), |recv| async move {
+            let recv = recv?;
+            match recv.recv().await {
+                Ok(msgs) => Some((Ok(msgs), Some(recv))),
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+        .boxed()
+    }
+}
 pub struct TryIter<'a, T: Receiver> {
     recv: &'a mut T,
 }
@@ -315,9 +623,37 @@ impl<T: PacketReceiver> Receiver for T {
 }
 */
 
+/// The asynchronous counterpart of [`Sender`](./trait.Sender.html).
+pub trait AsyncSender {
+    fn send<'a>(
+        &'a mut self,
+        msgs: &'a mut [LedMsg],
+        is_time_offset: bool,
+    ) -> BoxFuture<'a, Result<(), Error>>;
+    fn get_time(&self) -> u64;
+    /// See [`Sender::presentation_delay`](./trait.Sender.html#method.presentation_delay).
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        Err(Error::Misc(
+            "This sender can't query the presentation delay.".to_string(),
+        ))
+    }
+}
+
 pub trait Sender {
     fn send(&mut self, msgs: &mut [LedMsg], is_time_offset: bool) -> Result<(), Error>;
     fn get_time(&self) -> u64;
+    /// Returns the presentation delay of the receiver, which is how far behind the
+    /// synchronized time the receiver shows messages.
+    /// Returns an error if the sender can't query the receiver.
+    ///
+    /// Only the local channels support the query. The Bluetooth and packet radio transports
+    /// don't carry the delay, so their senders return an error and have to be told the delay
+    /// by other means, such as the configuration of the show.
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        Err(Error::Misc(
+            "This sender can't query the presentation delay.".to_string(),
+        ))
+    }
 }
 /*
 pub struct HamSender<T: PacketSender> {
@@ -354,6 +690,8 @@ impl<T: PacketSender> Sender for T {
 pub struct LocalReceiver {
     start: Instant,
     recv: mpsc::Receiver<Vec<LedMsg>>,
+    /// The presentation delay in microseconds, shared with the senders.
+    delay: Arc<AtomicU64>,
 }
 
 impl Receiver for LocalReceiver {
@@ -379,11 +717,17 @@ impl Receiver for LocalReceiver {
         })?;
         Ok(msgs)
     }
+    #[inline]
+    fn set_presentation_delay(&mut self, delay: Duration) {
+        self.delay
+            .store(delay.as_micros() as u64, Ordering::Relaxed);
+    }
 }
 
 pub struct LocalSender {
     start: Instant,
     sender: mpsc::SyncSender<Vec<LedMsg>>,
+    delay: Arc<AtomicU64>,
 }
 impl Sender for LocalSender {
     #[inline]
@@ -401,10 +745,95 @@ impl Sender for LocalSender {
     fn get_time(&self) -> u64 {
         self.start.elapsed().as_micros() as u64
     }
+    #[inline]
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        Ok(Duration::from_micros(self.delay.load(Ordering::Relaxed)))
+    }
 }
 
 pub fn channel(size: usize) -> (LocalSender, LocalReceiver) {
     let (sender, recv) = mpsc::sync_channel(size);
     let start = Instant::now();
-    (LocalSender { start, sender }, LocalReceiver { start, recv })
+    let delay = Arc::new(AtomicU64::new(0));
+    (
+        LocalSender {
+            start,
+            sender,
+            delay: delay.clone(),
+        },
+        LocalReceiver { start, recv, delay },
+    )
+}
+
+pub struct AsyncLocalReceiver {
+    start: Instant,
+    recv: async_mpsc::Receiver<Vec<LedMsg>>,
+    delay: Arc<AtomicU64>,
+}
+impl AsyncReceiver for AsyncLocalReceiver {
+    #[inline]
+    fn cur_time(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+    fn recv(&mut self) -> BoxFuture<'_, Result<Vec<LedMsg>, Error>> {
+        Box::pin(async move {
+            self.recv.next().await.ok_or_else(|| {
+                Error::Unrecoverable("AsyncLocalReceiver: senders disconnected".to_string())
+            })
+        })
+    }
+    #[inline]
+    fn set_presentation_delay(&mut self, delay: Duration) {
+        self.delay
+            .store(delay.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncLocalSender {
+    start: Instant,
+    sender: async_mpsc::Sender<Vec<LedMsg>>,
+    delay: Arc<AtomicU64>,
+}
+impl AsyncSender for AsyncLocalSender {
+    fn send<'a>(
+        &'a mut self,
+        msgs: &'a mut [LedMsg],
+        is_msg_offset: bool,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        if is_msg_offset {
+            let cur_time = self.get_time();
+            for msg in msgs.iter_mut() {
+                msg.time = cur_time.wrapping_add(msg.time);
+            }
+        }
+        let msgs = Vec::from(&msgs[..]);
+        Box::pin(async move {
+            self.sender.send(msgs).await.map_err(|_| {
+                Error::Unrecoverable("AsyncLocalSender: receiver disconnected".to_string())
+            })
+        })
+    }
+    fn get_time(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+    #[inline]
+    fn presentation_delay(&self) -> Result<Duration, Error> {
+        Ok(Duration::from_micros(self.delay.load(Ordering::Relaxed)))
+    }
+}
+
+/// Creates an asynchronous local transport, like [`channel`](./fn.channel.html).
+pub fn async_channel(size: usize) -> (AsyncLocalSender, AsyncLocalReceiver) {
+    let (sender, recv) = async_mpsc::channel(size);
+    let start = Instant::now();
+    let delay = Arc::new(AtomicU64::new(0));
+    (
+        AsyncLocalSender {
+            start,
+            sender,
+            delay: delay.clone(),
+        },
+        AsyncLocalReceiver { start, recv, delay },
+    )
 }
@@ -0,0 +1,188 @@
+//! Combines several [`Receiver`]s into one so that a fixture can listen to multiple transports.
+//!
+//! Each source of a [`MergedReceiver`] has a priority. While a source is sending,
+//! messages from sources with a lower priority are dropped. When it goes quiet for
+//! longer than the [`failover`] period, the next highest priority source takes over,
+//! so a backup controller can keep a rig running if the primary is lost.
+//!
+//! Message times are converted into the clock of the highest priority source that hasn't
+//! failed, so messages from that source keep the time their sender gave them, and effects
+//! seeded by the time, such as [`Noise`], match fixtures listening to that source directly.
+//!
+//! [`Receiver`]: ../trait.Receiver.html
+//! [`MergedReceiver`]: ./struct.MergedReceiver.html
+//! [`failover`]: ./struct.MergedReceiver.html#structfield.failover
+//! [`Noise`]: ../noise/struct.Noise.html
+use crate::{Error, LedMsg, Receiver};
+use std::time::{Duration, Instant};
+
+struct Source {
+    recv: Box<dyn Receiver + Send>,
+    priority: u8,
+    last_packet: Option<Instant>,
+    /// Set once the source has returned an unrecoverable error.
+    failed: bool,
+}
+
+/// A [`Receiver`](../trait.Receiver.html) that polls several receivers and converts
+/// their messages into a single time base.
+pub struct MergedReceiver {
+    sources: Vec<Source>,
+    /// How long a source must be quiet before lower priority sources take over.
+    pub failover: Duration,
+    /// How long to sleep between polls of the sources when none have messages.
+    pub poll_interval: Duration,
+    pub verbose: u8,
+}
+impl Default for MergedReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MergedReceiver {
+    pub fn new() -> Self {
+        MergedReceiver {
+            sources: Vec::new(),
+            failover: Duration::from_secs(1),
+            poll_interval: Duration::from_millis(1),
+            verbose: 0,
+        }
+    }
+    /// Adds a source with the given priority and returns its index.
+    /// Higher priorities take precedence, and sources with equal priorities are merged.
+    pub fn add<R: Receiver + Send + 'static>(&mut self, recv: R, priority: u8) -> usize {
+        self.sources.push(Source {
+            recv: Box::new(recv),
+            priority,
+            last_packet: None,
+            failed: false,
+        });
+        self.sources.len() - 1
+    }
+    /// Returns the number of sources, including ones that have failed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+    /// Returns the index of the highest priority source that has sent messages within the
+    /// failover period, or `None` if every source is quiet.
+    pub fn active(&self) -> Option<usize> {
+        let now = Instant::now();
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| self.is_live(s, now))
+            .max_by_key(|(i, s)| (s.priority, std::cmp::Reverse(*i)))
+            .map(|(i, _)| i)
+    }
+    /// Returns the index of the source whose clock is the merged time base.
+    /// This is the highest priority source that hasn't failed, with ties going to the
+    /// source added first.
+    fn clock(&self) -> Option<usize> {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.failed)
+            .max_by_key(|(i, s)| (s.priority, std::cmp::Reverse(*i)))
+            .map(|(i, _)| i)
+    }
+    fn is_live(&self, source: &Source, now: Instant) -> bool {
+        !source.failed
+            && source
+                .last_packet
+                .is_some_and(|l| now.saturating_duration_since(l) < self.failover)
+    }
+    /// Polls each source once, returning the accepted messages in the merged time base.
+    fn poll(&mut self) -> Result<Vec<LedMsg>, Error> {
+        let clock = self.clock();
+        let cur_time = self.cur_time();
+        let mut batches = Vec::new();
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            if source.failed {
+                continue;
+            }
+            match source.recv.try_recv() {
+                Ok(mut msgs) => {
+                    // Each source has its own clock, so the messages of the other sources
+                    // are shifted by the difference between their clock and the time base.
+                    if clock != Some(i) {
+                        let offset = source.recv.cur_time().wrapping_sub(cur_time);
+                        for msg in msgs.iter_mut() {
+                            msg.time = msg.time.wrapping_sub(offset);
+                        }
+                    }
+                    source.last_packet = Some(Instant::now());
+                    batches.push((i, msgs));
+                }
+                Err(Error::Timeout(_)) => (),
+                Err(e) => {
+                    if self.verbose >= 1 {
+                        eprintln!("MergedReceiver: source {} failed: {:?}", i, e);
+                    }
+                    source.failed = true;
+                }
+            }
+        }
+        if self.sources.iter().all(|s| s.failed) {
+            return Err(Error::Unrecoverable(
+                "MergedReceiver: all sources have failed".to_string(),
+            ));
+        }
+        // Messages are only accepted from sources that aren't outranked by a live source.
+        let now = Instant::now();
+        let top = self
+            .sources
+            .iter()
+            .filter(|s| self.is_live(s, now))
+            .map(|s| s.priority)
+            .max();
+        let mut ret = Vec::new();
+        for (i, msgs) in batches {
+            if Some(self.sources[i].priority) >= top {
+                ret.extend(msgs);
+            } else if self.verbose >= 3 {
+                eprintln!("MergedReceiver: dropping messages from source {}", i);
+            }
+        }
+        Ok(ret)
+    }
+}
+impl Receiver for MergedReceiver {
+    /// Returns the time of the highest priority source that hasn't failed,
+    /// or zero if there are none.
+    #[inline]
+    fn cur_time(&self) -> u64 {
+        self.clock().map_or(0, |i| self.sources[i].recv.cur_time())
+    }
+    fn recv_to(&mut self, timeout: Duration) -> Result<Vec<LedMsg>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let msgs = self.poll()?;
+            if !msgs.is_empty() {
+                return Ok(msgs);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout("MergedReceiver: recv timeout".to_string()));
+            }
+            std::thread::sleep(self.poll_interval.min(deadline - now));
+        }
+    }
+    fn recv(&mut self) -> Result<Vec<LedMsg>, Error> {
+        loop {
+            match self.recv_to(Duration::from_secs(1)) {
+                Err(Error::Timeout(_)) => continue,
+                res => return res,
+            }
+        }
+    }
+    fn set_presentation_delay(&mut self, delay: Duration) {
+        for source in self.sources.iter_mut() {
+            source.recv.set_presentation_delay(delay);
+        }
+    }
+}
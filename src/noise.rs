@@ -0,0 +1,93 @@
+//! Deterministic, clock-synchronized randomness for effects.
+//!
+//! Receivers must not use their own random number generators for effects,
+//! otherwise receivers that are time synchronized would still drift apart visually.
+//! Instead, [`Noise`] is seeded from the message being rendered and indexed by
+//! LED position and time slot, so every receiver computes the same values for the same cue.
+//!
+//! [`Noise`]: ./struct.Noise.html
+use crate::{Command, LedMsg};
+
+/// splitmix64 finalizer
+#[inline]
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+/// Converts the high bits of `v` to a value in the range [0, 1).
+#[inline]
+fn to_unit(v: u64) -> f32 {
+    (v >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// A random-access source of noise seeded from a message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Noise {
+    seed: u64,
+}
+impl Noise {
+    /// Creates noise for `element` started at `time` with the user given `seed`.
+    pub fn new(element: u8, time: u64, seed: u8) -> Self {
+        let seed = mix(time ^ ((element as u64) << 56) ^ ((seed as u64) << 48));
+        Noise { seed }
+    }
+    /// Creates the noise for `msg`.
+    /// Commands that don't carry a seed use a seed of zero.
+    pub fn from_msg(msg: &LedMsg) -> Self {
+        let seed = match msg.cmd {
+            Command::Sparkle { seed, .. } | Command::Fire { seed, .. } => seed,
+            _ => 0,
+        };
+        Noise::new(msg.element, msg.time, seed)
+    }
+    /// Returns a random value for the LED at `pos` during time `slot`.
+    #[inline]
+    pub fn value(&self, pos: usize, slot: u64) -> u64 {
+        mix(self
+            .seed
+            .wrapping_add((pos as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .wrapping_add(slot.wrapping_mul(0xD6E8_FEB8_6659_FD93)))
+    }
+    /// Returns a random value in the range [0, 1) for the LED at `pos` during time `slot`.
+    #[inline]
+    pub fn unit(&self, pos: usize, slot: u64) -> f32 {
+        to_unit(self.value(pos, slot))
+    }
+    /// Returns a value in the range [0, 1) that changes smoothly over time.
+    /// `frac` is how far into `slot` the time is, and should be in the range [0, 1).
+    #[inline]
+    pub fn smooth(&self, pos: usize, slot: u64, frac: f32) -> f32 {
+        // smoothstep between the values of the adjacent slots
+        let t = frac * frac * (3.0 - 2.0 * frac);
+        self.unit(pos, slot) * (1.0 - t) + self.unit(pos, slot.wrapping_add(1)) * t
+    }
+    /// Returns a random number generator for the LED at `pos` during time `slot`,
+    /// for effects that need several random values per LED.
+    #[inline]
+    pub fn rng(&self, pos: usize, slot: u64) -> NoiseRng {
+        NoiseRng {
+            state: self.value(pos, slot),
+        }
+    }
+}
+
+/// A sequence of random values created by [`Noise::rng`].
+///
+/// [`Noise::rng`]: ./struct.Noise.html#method.rng
+#[derive(Clone, Debug)]
+pub struct NoiseRng {
+    state: u64,
+}
+impl NoiseRng {
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+    /// Returns a value in the range [0, 1).
+    #[inline]
+    pub fn next_f32(&mut self) -> f32 {
+        to_unit(self.next_u64())
+    }
+}
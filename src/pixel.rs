@@ -0,0 +1,231 @@
+//! Describes how a controller stores the color of each LED.
+//!
+//! LED drivers differ in the order they expect the channels in, whether the LEDs have
+//! a white channel, such as SK6812 RGBW strips, and how many bits each channel has.
+//! Each output of a [`Controller`] declares its [`PixelFormat`], and the [`Renderer`]
+//! converts its frames into it.
+//!
+//! [`Controller`]: ../controller/trait.Controller.html
+//! [`PixelFormat`]: ./struct.PixelFormat.html
+//! [`Renderer`]: ../controller/struct.Renderer.html
+use crate::color::ColorF;
+use crate::Error;
+use std::str::FromStr;
+
+/// What a byte, or pair of bytes, of an LED holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    White,
+    /// Unused. It is always set to zero.
+    Padding,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    /// Each channel is two bytes with the most significant byte first.
+    Sixteen,
+}
+impl BitDepth {
+    #[inline]
+    pub fn bytes(self) -> usize {
+        match self {
+            BitDepth::Eight => 1,
+            BitDepth::Sixteen => 2,
+        }
+    }
+    /// Returns the value of a channel at full brightness.
+    #[inline]
+    pub fn max(self) -> u16 {
+        match self {
+            BitDepth::Eight => u8::MAX as u16,
+            BitDepth::Sixteen => u16::MAX,
+        }
+    }
+}
+
+/// The layout of the bytes of each LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    channels: [Channel; 4],
+    len: usize,
+    pub depth: BitDepth,
+}
+impl Default for PixelFormat {
+    #[inline]
+    fn default() -> Self {
+        PixelFormat::BGRX
+    }
+}
+impl PixelFormat {
+    /// Blue, green and red followed by an unused byte.
+    /// This is the format of `rs_ws281x` for strips without a white channel,
+    /// and of controllers that don't declare a format.
+    pub const BGRX: PixelFormat = PixelFormat::new_const(
+        [
+            Channel::Blue,
+            Channel::Green,
+            Channel::Red,
+            Channel::Padding,
+        ],
+        4,
+    );
+    /// Blue, green, red and white.
+    /// This is the format of `rs_ws281x` for SK6812 RGBW strips.
+    pub const BGRW: PixelFormat = PixelFormat::new_const(
+        [Channel::Blue, Channel::Green, Channel::Red, Channel::White],
+        4,
+    );
+    pub const RGB: PixelFormat = PixelFormat::new_const(
+        [
+            Channel::Red,
+            Channel::Green,
+            Channel::Blue,
+            Channel::Padding,
+        ],
+        3,
+    );
+    /// The order WS2812 LEDs receive their channels in.
+    pub const GRB: PixelFormat = PixelFormat::new_const(
+        [
+            Channel::Green,
+            Channel::Red,
+            Channel::Blue,
+            Channel::Padding,
+        ],
+        3,
+    );
+    pub const RGBW: PixelFormat = PixelFormat::new_const(
+        [Channel::Red, Channel::Green, Channel::Blue, Channel::White],
+        4,
+    );
+    /// The order SK6812 RGBW LEDs receive their channels in.
+    pub const GRBW: PixelFormat = PixelFormat::new_const(
+        [Channel::Green, Channel::Red, Channel::Blue, Channel::White],
+        4,
+    );
+    const fn new_const(channels: [Channel; 4], len: usize) -> Self {
+        PixelFormat {
+            channels,
+            len,
+            depth: BitDepth::Eight,
+        }
+    }
+    /// Creates a format from the channels of each LED, in the order they are stored.
+    /// There must be between one and four channels, and each color channel can only be used once.
+    pub fn new(channels: &[Channel], depth: BitDepth) -> Result<Self, Error> {
+        if channels.is_empty() || channels.len() > 4 {
+            return Err(Error::BadInput(format!(
+                "Pixel formats must have one to four channels, not {}.",
+                channels.len()
+            )));
+        }
+        for (i, c) in channels.iter().enumerate() {
+            if *c != Channel::Padding && channels[..i].contains(c) {
+                return Err(Error::BadInput(format!(
+                    "Channel {:?} is used more than once.",
+                    c
+                )));
+            }
+        }
+        let mut ret = PixelFormat::new_const([Channel::Padding; 4], channels.len());
+        ret.channels[..channels.len()].copy_from_slice(channels);
+        ret.depth = depth;
+        Ok(ret)
+    }
+    /// Returns the format with each channel stored in `depth`.
+    #[inline]
+    pub fn with_depth(mut self, depth: BitDepth) -> Self {
+        self.depth = depth;
+        self
+    }
+    #[inline]
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels[..self.len]
+    }
+    #[inline]
+    pub fn has_white(&self) -> bool {
+        self.channels().contains(&Channel::White)
+    }
+    #[inline]
+    pub fn bytes_per_led(&self) -> usize {
+        self.len * self.depth.bytes()
+    }
+    /// Splits `color` into the red, green, blue and white channels of an LED.
+    /// If the format has a white channel, the white shared by the color channels is moved
+    /// to it, otherwise white is always zero.
+    pub fn split(&self, color: ColorF) -> [f32; 4] {
+        let [r, g, b] = [color.red, color.green, color.blue];
+        if self.has_white() {
+            let w = r.min(g).min(b).max(0.0);
+            [r - w, g - w, b - w, w]
+        } else {
+            [r, g, b, 0.0]
+        }
+    }
+    /// Writes the red, green, blue and white `values` of an LED into `buf`,
+    /// which must be `bytes_per_led` long.
+    pub fn write(&self, values: [u16; 4], buf: &mut [u8]) {
+        let size = self.depth.bytes();
+        for (c, bytes) in self.channels().iter().zip(buf.chunks_exact_mut(size)) {
+            let v = match c {
+                Channel::Red => values[0],
+                Channel::Green => values[1],
+                Channel::Blue => values[2],
+                Channel::White => values[3],
+                Channel::Padding => 0,
+            };
+            match self.depth {
+                BitDepth::Eight => bytes[0] = v as u8,
+                BitDepth::Sixteen => bytes.copy_from_slice(&v.to_be_bytes()),
+            }
+        }
+    }
+    /// Reads the red, green, blue and white values of an LED from `buf`.
+    /// Channels the format doesn't have are zero.
+    pub fn read(&self, buf: &[u8]) -> [u16; 4] {
+        let size = self.depth.bytes();
+        let mut ret = [0; 4];
+        for (c, bytes) in self.channels().iter().zip(buf.chunks_exact(size)) {
+            let v = match self.depth {
+                BitDepth::Eight => bytes[0] as u16,
+                BitDepth::Sixteen => u16::from_be_bytes([bytes[0], bytes[1]]),
+            };
+            match c {
+                Channel::Red => ret[0] = v,
+                Channel::Green => ret[1] = v,
+                Channel::Blue => ret[2] = v,
+                Channel::White => ret[3] = v,
+                Channel::Padding => (),
+            }
+        }
+        ret
+    }
+}
+impl FromStr for PixelFormat {
+    type Err = Error;
+    /// Parses the channels of a format from their initials, such as `grbw`,
+    /// with `x` for padding. A `16` suffix selects 16-bit channels.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let (s, depth) = match s.strip_suffix("16") {
+            Some(s) => (s, BitDepth::Sixteen),
+            None => (s.as_str(), BitDepth::Eight),
+        };
+        let channels = s
+            .chars()
+            .map(|c| match c {
+                'r' => Ok(Channel::Red),
+                'g' => Ok(Channel::Green),
+                'b' => Ok(Channel::Blue),
+                'w' => Ok(Channel::White),
+                'x' => Ok(Channel::Padding),
+                c => Err(Error::BadInput(format!("Unknown channel: {}", c))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        PixelFormat::new(&channels, depth)
+    }
+}
@@ -0,0 +1,81 @@
+//! Estimates and limits the current drawn by the LEDs.
+//!
+//! Long strips at full white draw far more current than small supplies can deliver.
+//! A [`PowerLimit`] models the current drawn by each channel of an LED, and the
+//! [`Renderer`] uses it to scale each frame down until it fits within the supply's budget.
+//!
+//! [`PowerLimit`]: ./struct.PowerLimit.html
+//! [`Renderer`]: ../controller/struct.Renderer.html
+use crate::color::ColorF;
+
+/// A model of the current drawn by a strip of LEDs and the budget it must stay within.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerLimit {
+    /// The current in milliamps drawn by the red, green and blue channels of one LED at full brightness.
+    pub channel_ma: [f32; 3],
+    /// The current in milliamps drawn by the white channel of one LED at full brightness,
+    /// for LEDs that have one.
+    pub white_ma: f32,
+    /// The current in milliamps drawn by one LED when it is off.
+    pub idle_ma: f32,
+    /// The maximum current in milliamps that the supply can deliver to the strip.
+    pub budget_ma: f32,
+}
+impl Default for PowerLimit {
+    /// Returns a model of WS2812 LEDs with a 2A budget.
+    fn default() -> Self {
+        PowerLimit {
+            channel_ma: [20.0; 3],
+            white_ma: 20.0,
+            idle_ma: 1.0,
+            budget_ma: 2000.0,
+        }
+    }
+}
+impl PowerLimit {
+    /// Estimates the current in milliamps drawn when showing `leds` on LEDs without
+    /// a white channel.
+    pub fn estimate(&self, leds: &[ColorF]) -> f32 {
+        self.estimate_channels(leds.iter().map(|c| [c.red, c.green, c.blue, 0.0]))
+    }
+    /// Estimates the current in milliamps drawn by LEDs with the given red, green, blue
+    /// and white channels, such as from [`PixelFormat::split`].
+    ///
+    /// [`PixelFormat::split`]: ../pixel/struct.PixelFormat.html#method.split
+    pub fn estimate_channels<I: IntoIterator<Item = [f32; 4]>>(&self, leds: I) -> f32 {
+        let [r, g, b] = self.channel_ma;
+        leds.into_iter()
+            .map(|[red, green, blue, white]| {
+                let ch = |v: f32| v.clamp(0.0, 1.0);
+                self.idle_ma
+                    + ch(red) * r
+                    + ch(green) * g
+                    + ch(blue) * b
+                    + ch(white) * self.white_ma
+            })
+            .sum()
+    }
+    /// Returns the factor that the channels must be scaled by so that a frame
+    /// estimated to draw `estimate` milliamps of `leds` LEDs fits within the budget.
+    pub fn scale(&self, estimate: f32, leds: usize) -> f32 {
+        let idle = self.idle_ma * leds as f32;
+        if estimate <= self.budget_ma || estimate <= idle {
+            1.0
+        } else {
+            // the idle current can't be reduced, so only the remainder is scaled
+            ((self.budget_ma - idle) / (estimate - idle)).max(0.0)
+        }
+    }
+}
+
+/// The current drawn by the last frame that was output.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PowerEstimate {
+    /// The current in milliamps the frame would have drawn without limiting.
+    pub requested_ma: f32,
+    /// The current in milliamps the frame draws after limiting.
+    pub output_ma: f32,
+    /// The factor the frame was scaled by to fit within the budget.
+    /// This is one when the frame wasn't limited.
+    pub scale: f32,
+}
@@ -0,0 +1,53 @@
+//! The async runtime used by the transports and the renderer's async loop.
+//!
+//! The runtime is selected with the **async-std** (default) or **tokio** feature.
+//! If both are enabled, tokio is used.
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "async-std", feature = "tokio")))]
+compile_error!("One of the async-std or tokio features must be enabled.");
+
+/// The error returned when a future passed to [`timeout`] doesn't complete in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+/// Waits for `dur` without blocking the executor.
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(dur: Duration) {
+    tokio::time::sleep(dur).await
+}
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) async fn sleep(dur: Duration) {
+    async_std::task::sleep(dur).await
+}
+
+/// Awaits `fut`, giving up after `dur`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn timeout<F: Future>(dur: Duration, fut: F) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout(dur, fut).await.map_err(|_| Elapsed)
+}
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) async fn timeout<F: Future>(dur: Duration, fut: F) -> Result<F::Output, Elapsed> {
+    async_std::future::timeout(dur, fut)
+        .await
+        .map_err(|_| Elapsed)
+}
+
+/// Runs `fut` to completion on the current thread.
+/// This is used by the blocking transport APIs and must not be called from within the runtime.
+#[cfg(feature = "tokio")]
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    use tokio::runtime::{Builder, Runtime};
+    thread_local! {
+        static RUNTIME: Runtime = Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("Failed to build the tokio runtime.");
+    }
+    RUNTIME.with(|rt| rt.block_on(fut))
+}
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    async_std::task::block_on(fut)
+}
@@ -0,0 +1,128 @@
+//! Limits the rate of flashes shown to an audience.
+//!
+//! Rapid flashing can trigger seizures in people with photosensitive epilepsy,
+//! so the [`Renderer`] can optionally pass every frame through a [`FlashLimiter`].
+//! The limiter splits the strip into regions and watches each region's luminance.
+//! Once a region has made as many large luminance transitions as it is allowed
+//! within the last second, further changes to it are attenuated until older transitions expire,
+//! regardless of what senders request.
+//!
+//! [`Renderer`]: ../controller/struct.Renderer.html
+//! [`FlashLimiter`]: ./struct.FlashLimiter.html
+use crate::color::ColorF;
+use std::collections::VecDeque;
+
+/// The window in microseconds that flashes are counted over.
+const WINDOW: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Default)]
+struct Region {
+    /// The luminance at the extreme of the current transition.
+    /// Changes continuing in the same direction move the extreme with them.
+    level: f32,
+    /// Whether the current transition is rising, or `None` before the first transition.
+    rising: Option<bool>,
+    /// The times of the transitions within the window.
+    transitions: VecDeque<u64>,
+}
+
+/// Attenuates flashes that exceed a maximum frequency.
+///
+/// A flash is a pair of opposing transitions, so a region may make up to twice
+/// `max_flashes` transitions per second. A transition is counted when a region's luminance
+/// moves by at least `threshold` against the direction of the previous transition,
+/// so fades in one direction are a single transition however fast they are.
+#[derive(Debug, Clone)]
+pub struct FlashLimiter {
+    /// The maximum number of flashes allowed in any second.
+    pub max_flashes: f32,
+    /// The change in a region's average relative luminance, from 0 to 1,
+    /// that counts as a transition.
+    pub threshold: f32,
+    /// The number of LEDs in each region.
+    pub region_len: usize,
+    regions: Vec<Region>,
+    prev: Vec<ColorF>,
+}
+impl Default for FlashLimiter {
+    /// Returns a limiter allowing 3 flashes per second with a threshold of 10% luminance.
+    fn default() -> Self {
+        FlashLimiter::new(3.0, 0.1, 16)
+    }
+}
+impl FlashLimiter {
+    pub fn new(max_flashes: f32, threshold: f32, region_len: usize) -> Self {
+        FlashLimiter {
+            max_flashes,
+            threshold,
+            region_len,
+            regions: Vec::new(),
+            prev: Vec::new(),
+        }
+    }
+    /// Limits the flashes of `leds`, which is the frame shown at `time` in microseconds.
+    /// Frames must be passed in order.
+    pub fn apply(&mut self, time: u64, leds: &mut [ColorF]) {
+        let region_len = self.region_len.max(1);
+        let max_transitions = (2.0 * self.max_flashes).floor() as usize;
+        let avg =
+            |leds: &[ColorF]| leds.iter().map(|c| c.luminance()).sum::<f32>() / leds.len() as f32;
+        if self.prev.len() != leds.len() {
+            // start from the first frame rather than from black
+            self.prev.clear();
+            self.prev.extend_from_slice(leds);
+            self.regions.clear();
+            self.regions
+                .extend(leds.chunks(region_len).map(|leds| Region {
+                    level: avg(leds),
+                    ..Region::default()
+                }));
+        }
+        for ((region, leds), prev) in self
+            .regions
+            .iter_mut()
+            .zip(leds.chunks_mut(region_len))
+            .zip(self.prev.chunks_mut(region_len))
+        {
+            while let Some(t) = region.transitions.front() {
+                if time.wrapping_sub(*t) >= WINDOW {
+                    region.transitions.pop_front();
+                } else {
+                    break;
+                }
+            }
+            let target = avg(leds);
+            let rising = target > region.level;
+            if region.rising == Some(rising) {
+                // continuing the current transition
+                region.level = target;
+                prev.copy_from_slice(leds);
+                continue;
+            }
+            if (target - region.level).abs() < self.threshold {
+                prev.copy_from_slice(leds);
+                continue;
+            }
+            if region.transitions.len() < max_transitions {
+                region.transitions.push_back(time);
+                region.level = target;
+                region.rising = Some(rising);
+                prev.copy_from_slice(leds);
+                continue;
+            }
+            // Too many transitions, so move from the previous frame only as far as
+            // possible without making another transition.
+            let from = avg(prev);
+            let limit = region.level + (target - region.level).signum() * self.threshold * 0.99;
+            let t = if (target - from).abs() > f32::EPSILON {
+                ((limit - from) / (target - from)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            for (l, p) in leds.iter_mut().zip(prev.iter_mut()) {
+                *l = p.lerp(*l, t);
+                *p = *l;
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Statistics about the frames rendered by the [`Renderer`]'s frame loop.
+//!
+//! [`Renderer`]: ../controller/struct.Renderer.html
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// What the frame loop does when a frame takes longer than the frame period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overrun {
+    /// Drops the frames whose start times have already passed,
+    /// so the loop resumes at the next frame boundary.
+    Skip,
+    /// Renders frames back to back until the loop has caught up with its schedule.
+    CatchUp,
+}
+impl Default for Overrun {
+    #[inline]
+    fn default() -> Self {
+        Overrun::Skip
+    }
+}
+
+/// Statistics collected by the frame loop over one period.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    /// The duration the statistics were collected over.
+    pub period: Duration,
+    /// The number of frames rendered.
+    pub frames: u32,
+    pub fps: f64,
+    /// Percentiles of the time between the starts of successive frames.
+    pub frame_time_p50: Duration,
+    pub frame_time_p95: Duration,
+    pub frame_time_p99: Duration,
+    pub frame_time_max: Duration,
+    /// The mean and maximum time taken to render a frame.
+    pub render_time_mean: Duration,
+    pub render_time_max: Duration,
+    /// The number of frames that finished after the next frame should have started.
+    pub missed_deadlines: u32,
+    /// The number of frames dropped by [`Overrun::Skip`](./enum.Overrun.html#variant.Skip).
+    pub skipped_frames: u32,
+    /// The number of messages that started being shown.
+    pub msgs_applied: u64,
+    /// The number of messages dropped for being outside the time window.
+    pub msgs_expired: u64,
+}
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FPS: {:.1}, frame time p50/p95/p99/max: {:?}/{:?}/{:?}/{:?}, render time mean/max: {:?}/{:?}, \
+             missed: {}, skipped: {}, msgs applied: {}, msgs expired: {}",
+            self.fps,
+            self.frame_time_p50,
+            self.frame_time_p95,
+            self.frame_time_p99,
+            self.frame_time_max,
+            self.render_time_mean,
+            self.render_time_max,
+            self.missed_deadlines,
+            self.skipped_frames,
+            self.msgs_applied,
+            self.msgs_expired
+        )
+    }
+}
+
+/// Accumulates the timings of frames until a period is finished.
+pub(crate) struct StatsCollector {
+    start: Instant,
+    frame_times: Vec<Duration>,
+    render_times: Vec<Duration>,
+    pub(crate) missed_deadlines: u32,
+    pub(crate) skipped_frames: u32,
+    /// The renderer's message counters at the start of the period.
+    applied: u64,
+    expired: u64,
+}
+impl StatsCollector {
+    pub(crate) fn new(start: Instant, applied: u64, expired: u64) -> Self {
+        StatsCollector {
+            start,
+            frame_times: Vec::new(),
+            render_times: Vec::new(),
+            missed_deadlines: 0,
+            skipped_frames: 0,
+            applied,
+            expired,
+        }
+    }
+    #[inline]
+    pub(crate) fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.start)
+    }
+    /// Records a frame that took `render_time` to render and started `frame_time`
+    /// after the previous frame, if there was one.
+    pub(crate) fn record(&mut self, frame_time: Option<Duration>, render_time: Duration) {
+        self.frame_times.extend(frame_time);
+        self.render_times.push(render_time);
+    }
+    /// Returns the statistics of the period ending at `now` and starts a new period.
+    /// `applied` and `expired` are the renderer's current message counters.
+    pub(crate) fn finish(&mut self, now: Instant, applied: u64, expired: u64) -> FrameStats {
+        let period = self.elapsed(now);
+        let frames = self.render_times.len() as u32;
+        self.frame_times.sort_unstable();
+        let percentile = |p: f64| {
+            let times = &self.frame_times;
+            if times.is_empty() {
+                Duration::from_secs(0)
+            } else {
+                times[((times.len() - 1) as f64 * p).round() as usize]
+            }
+        };
+        let render_total: Duration = self.render_times.iter().sum();
+        let stats = FrameStats {
+            period,
+            frames,
+            fps: frames as f64 / period.as_secs_f64().max(f64::MIN_POSITIVE),
+            frame_time_p50: percentile(0.5),
+            frame_time_p95: percentile(0.95),
+            frame_time_p99: percentile(0.99),
+            frame_time_max: percentile(1.0),
+            render_time_mean: render_total.checked_div(frames).unwrap_or_default(),
+            render_time_max: self.render_times.iter().max().copied().unwrap_or_default(),
+            missed_deadlines: self.missed_deadlines,
+            skipped_frames: self.skipped_frames,
+            msgs_applied: applied - self.applied,
+            msgs_expired: expired - self.expired,
+        };
+        *self = StatsCollector::new(now, applied, expired);
+        stats
+    }
+}
+
+/// Schedules the frames of a frame loop on fixed boundaries and collects their statistics.
+pub(crate) struct FrameSchedule {
+    period: Duration,
+    /// When the next frame should start.
+    pub(crate) deadline: Instant,
+    last_start: Option<Instant>,
+    collector: StatsCollector,
+}
+impl FrameSchedule {
+    /// `applied` and `expired` are the renderer's current message counters.
+    pub(crate) fn new(target_fps: f64, applied: u64, expired: u64) -> Self {
+        let now = Instant::now();
+        FrameSchedule {
+            period: Duration::from_secs_f64(1.0 / target_fps),
+            deadline: now,
+            last_start: None,
+            collector: StatsCollector::new(now, applied, expired),
+        }
+    }
+    /// Schedules the next frame for now, such as after the loop was paused.
+    pub(crate) fn restart(&mut self) {
+        self.deadline = Instant::now();
+        self.last_start = None;
+    }
+    /// Records a frame that started at `start` and ended at `end`, and schedules the next frame.
+    /// Returns the statistics if `stats_period` has passed since they were last returned.
+    pub(crate) fn frame_done(
+        &mut self,
+        start: Instant,
+        end: Instant,
+        overrun: Overrun,
+        stats_period: Duration,
+        applied: u64,
+        expired: u64,
+    ) -> Option<FrameStats> {
+        let collector = &mut self.collector;
+        collector.record(self.last_start.map(|l| start - l), end - start);
+        self.last_start = Some(start);
+        self.deadline += self.period;
+        if end > self.deadline {
+            collector.missed_deadlines += 1;
+            if overrun == Overrun::Skip {
+                let behind =
+                    ((end - self.deadline).as_secs_f64() / self.period.as_secs_f64()) as u32 + 1;
+                collector.skipped_frames += behind;
+                self.deadline += self.period * behind;
+            }
+        }
+        if collector.elapsed(end) >= stats_period {
+            Some(collector.finish(end, applied, expired))
+        } else {
+            None
+        }
+    }
+}
@@ -1,6 +1,23 @@
-use crate::color::Color;
-use crate::{channel, Command, LedMsg, Receiver, Sender};
+use crate::color::{BlendMode, Calibration, Color, ColorF, ColorMap};
+use crate::controller::{Controller, Idle, MergePolicy, RenderRetry, Renderer, WithPixelFormats};
+use crate::easing::Easing;
+use crate::fanout::MultiSender;
+use crate::layout::{Direction, Segment, Symmetry};
+use crate::merge::MergedReceiver;
+use crate::noise::Noise;
+use crate::pixel::{BitDepth, PixelFormat};
+use crate::power::PowerLimit;
+use crate::safety::FlashLimiter;
+use crate::stats::StatsCollector;
+use crate::{
+    async_channel, channel, effects, AsyncReceiver, AsyncSender, Command, Error, LedMsg, Receiver,
+    Sender,
+};
+use futures::StreamExt;
 use rand::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 fn rng() -> StdRng {
     let mut seed = [0; 32];
@@ -10,34 +27,76 @@ fn rng() -> StdRng {
     StdRng::from_seed(seed)
 }
 fn generate_test_msgs() -> [LedMsg; 255] {
-    let mut test_vals = [LedMsg {
-        cmd: Command::Null,
-        time: 0,
-        color: 0,
-        element: 0,
-    }; 255];
+    let mut test_vals = [LedMsg::default(); 255];
     let mut rng = rng();
 
-    for (i, cmd) in [
+    let cmds = [
         Command::Null,
         Command::Flat(0),
         Command::FlatStack(0),
         Command::PulseLinear(0),
         Command::PulseQuadratic(0),
-    ]
-    .iter()
-    .enumerate()
-    {
-        for msg in test_vals[i * 51..(i + 1) * 51].iter_mut() {
-            let cmd = match cmd {
-                Command::Null => Command::Null,
-                Command::Flat(_) => Command::Flat(rng.gen()),
-                Command::FlatStack(_) => Command::FlatStack(rng.gen()),
-                Command::PulseLinear(_) => Command::PulseLinear(rng.gen()),
-                Command::PulseQuadratic(_) => Command::PulseQuadratic(rng.gen()),
-            };
-            msg.cmd = cmd;
-        }
+        Command::Wipe { period: 0 },
+        Command::Chase {
+            period: 0,
+            width: 0,
+        },
+        Command::Strobe { period: 0, duty: 0 },
+        Command::Rainbow {
+            period: 0,
+            spread: 0,
+        },
+        Command::Sparkle {
+            period: 0,
+            density: 0,
+            seed: 0,
+        },
+        Command::Fire {
+            period: 0,
+            intensity: 0,
+            seed: 0,
+        },
+        Command::Breathe { period: 0 },
+        Command::Blackout,
+        Command::Dimmer(0),
+        Command::Freeze,
+        Command::Resume,
+    ];
+    let per_cmd = test_vals.len() / cmds.len() + 1;
+    for (i, msg) in test_vals.iter_mut().enumerate() {
+        msg.cmd = match cmds[i / per_cmd] {
+            Command::Null => Command::Null,
+            Command::Flat(_) => Command::Flat(rng.gen()),
+            Command::FlatStack(_) => Command::FlatStack(rng.gen()),
+            Command::PulseLinear(_) => Command::PulseLinear(rng.gen()),
+            Command::PulseQuadratic(_) => Command::PulseQuadratic(rng.gen()),
+            Command::Wipe { .. } => Command::Wipe { period: rng.gen() },
+            Command::Chase { .. } => Command::Chase {
+                period: rng.gen(),
+                width: rng.gen(),
+            },
+            Command::Strobe { .. } => Command::Strobe {
+                period: rng.gen(),
+                duty: rng.gen(),
+            },
+            Command::Rainbow { .. } => Command::Rainbow {
+                period: rng.gen(),
+                spread: rng.gen(),
+            },
+            Command::Sparkle { .. } => Command::Sparkle {
+                period: rng.gen(),
+                density: rng.gen(),
+                seed: rng.gen(),
+            },
+            Command::Fire { .. } => Command::Fire {
+                period: rng.gen(),
+                intensity: rng.gen(),
+                seed: rng.gen(),
+            },
+            Command::Breathe { .. } => Command::Breathe { period: rng.gen() },
+            Command::Dimmer(_) => Command::Dimmer(rng.gen()),
+            cmd => cmd,
+        };
     }
     test_vals.shuffle(&mut rng);
     for msg in test_vals.iter_mut() {
@@ -51,6 +110,20 @@ fn generate_test_msgs() -> [LedMsg; 255] {
         };
         msg.element = rng.gen();
         msg.color = rng.gen();
+        if rng.gen() {
+            msg.blend_mode = BlendMode::from_u8(rng.gen_range(0, 5)).unwrap();
+        }
+        if rng.gen() {
+            msg.transition = Some(rng.gen());
+        }
+        if rng.gen() {
+            let params = rng.gen::<[u8; 4]>();
+            let (easing, _) = Easing::deserialize(rng.gen_range(1, 16), &params).unwrap();
+            msg.easing = Some(easing);
+        }
+        if rng.gen() {
+            msg.source = rng.gen();
+        }
     }
     test_vals
 }
@@ -76,8 +149,1237 @@ fn local_send_receive() {
     let test_vals = generate_test_msgs();
     let (mut sender, mut recv) = channel(1);
     for i in 0..5 {
-        sender.send(&test_vals[i * 51..(i + 1) * 51]).unwrap();
+        let mut msgs = test_vals;
+        sender.send(&mut msgs[i * 51..(i + 1) * 51], false).unwrap();
         let cpy = recv.recv().unwrap();
         assert_eq!(&test_vals[i * 51..(i + 1) * 51], &cpy[..]);
     }
 }
+
+#[test]
+fn effects_are_deterministic() {
+    let color = Color::ORANGE;
+    let mut msg = LedMsg {
+        time: 123_456_789,
+        element: 7,
+        color: 0,
+        cmd: Command::Null,
+        ..LedMsg::default()
+    };
+    let cmds = [
+        Command::Sparkle {
+            period: 200,
+            density: 64,
+            seed: 3,
+        },
+        Command::Fire {
+            period: 50,
+            intensity: 200,
+            seed: 3,
+        },
+    ];
+    for cmd in cmds.iter() {
+        msg.cmd = *cmd;
+        // two receivers rendering the same message
+        let a = Noise::from_msg(&msg);
+        let b = Noise::from_msg(&msg);
+        let mut reseeded = msg;
+        reseeded.cmd = match *cmd {
+            Command::Sparkle {
+                period, density, ..
+            } => Command::Sparkle {
+                period,
+                density,
+                seed: 4,
+            },
+            Command::Fire {
+                period, intensity, ..
+            } => Command::Fire {
+                period,
+                intensity,
+                seed: 4,
+            },
+            _ => unreachable!(),
+        };
+        let c = Noise::from_msg(&reseeded);
+        let mut differs = false;
+        for elapsed in (0..2_000_000).step_by(16_667) {
+            for pos in 0..64 {
+                let va = effects::eval(*cmd, color.into(), &a, None, elapsed, pos, 64);
+                let vb = effects::eval(*cmd, color.into(), &b, None, elapsed, pos, 64);
+                let vc = effects::eval(*cmd, color.into(), &c, None, elapsed, pos, 64);
+                assert!(va.is_some());
+                assert_eq!(va, vb);
+                differs |= va != vc;
+            }
+        }
+        assert!(differs, "Changing the seed didn't change {:?}", cmd);
+    }
+    let noise = Noise::from_msg(&msg);
+    let strobe = Command::Strobe {
+        period: 100,
+        duty: 128,
+    };
+    assert_eq!(
+        effects::eval(strobe, color.into(), &noise, None, 10_000, 0, 1),
+        Some(color.into())
+    );
+    assert_eq!(
+        effects::eval(strobe, color.into(), &noise, None, 60_000, 0, 1),
+        Some(ColorF::BLACK)
+    );
+    assert_eq!(
+        effects::eval(Command::FlatStack(0), color.into(), &noise, None, 0, 0, 1),
+        None
+    );
+}
+
+#[test]
+fn blend_modes() {
+    let base = Color {
+        red: 100,
+        green: 50,
+        blue: 0,
+        alpha: 255,
+    };
+    let light = Color {
+        red: 100,
+        green: 100,
+        blue: 100,
+        alpha: 0,
+    };
+    let blend =
+        |mode: BlendMode, dst: Color, src: Color| mode.blend(dst.into(), src.into()).to_color();
+    // zero alpha is additive light
+    assert_eq!(
+        blend(BlendMode::AlphaOver, base, light),
+        blend(BlendMode::Add, base, light)
+    );
+    // opaque colors cover what is below
+    assert_eq!(blend(BlendMode::AlphaOver, light, base), base);
+    // off pixels of an overlay are transparent
+    assert_eq!(blend(BlendMode::AlphaOver, base, Color::BLACK), base);
+    assert_eq!(blend(BlendMode::AlphaOver, base, base * 0.5).alpha, 255);
+    assert_eq!(blend(BlendMode::Multiply, base, Color::WHITE), base);
+    assert_eq!(blend(BlendMode::Multiply, base, Color::BLACK).red, 0);
+    assert_eq!(blend(BlendMode::Max, base, light).red, 100);
+    assert_eq!(blend(BlendMode::Max, base, light).blue, 100);
+    assert_eq!(blend(BlendMode::Replace, base, light), light);
+}
+
+type MsgQueue = Rc<RefCell<Vec<Vec<LedMsg>>>>;
+/// A `Receiver` whose clock is set by the test.
+struct ManualReceiver {
+    time: Rc<Cell<u64>>,
+    msgs: MsgQueue,
+}
+impl Receiver for ManualReceiver {
+    fn cur_time(&self) -> u64 {
+        self.time.get()
+    }
+    fn recv_to(&mut self, _timeout: Duration) -> Result<Vec<LedMsg>, Error> {
+        self.recv()
+    }
+    fn recv(&mut self) -> Result<Vec<LedMsg>, Error> {
+        let mut msgs = self.msgs.borrow_mut();
+        if msgs.is_empty() {
+            Err(Error::Timeout("ManualReceiver: no msgs".to_string()))
+        } else {
+            Ok(msgs.remove(0))
+        }
+    }
+}
+struct TestController(Vec<[u8; 4]>);
+impl Controller for TestController {
+    fn output_bytes(&self, _output: usize) -> &[u8] {
+        self.0.as_flattened()
+    }
+    fn output_bytes_mut(&mut self, _output: usize) -> &mut [u8] {
+        self.0.as_flattened_mut()
+    }
+    fn render(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+type TestRenderer = Renderer<ManualReceiver, TestController>;
+fn test_renderer(leds: usize) -> (TestRenderer, Rc<Cell<u64>>, MsgQueue) {
+    test_renderer_with(TestController(vec![[0; 4]; leds]))
+}
+fn test_renderer_with<C: Controller>(
+    ctl: C,
+) -> (Renderer<ManualReceiver, C>, Rc<Cell<u64>>, MsgQueue) {
+    let time = Rc::new(Cell::new(0));
+    let msgs = Rc::new(RefCell::new(Vec::new()));
+    let recv = ManualReceiver {
+        time: time.clone(),
+        msgs: msgs.clone(),
+    };
+    let mut renderer = Renderer::new(recv, ctl);
+    // exact output values are easier to check without dithering
+    renderer.dither = false;
+    (renderer, time, msgs)
+}
+
+#[test]
+fn transitions_are_frame_rate_independent() {
+    let mut results = Vec::new();
+    for fps in [30, 120].iter() {
+        let (mut renderer, time, msgs) = test_renderer(16);
+        renderer.color_map[1] = Color::WHITE;
+        renderer.transition = Duration::from_millis(100);
+        msgs.borrow_mut().push(vec![LedMsg {
+            time: 0,
+            element: 0,
+            color: 1,
+            cmd: Command::FlatStack(255),
+            ..LedMsg::default()
+        }]);
+        let frame = 1_000_000 / fps;
+        while time.get() < 50_000 {
+            renderer.update_leds().unwrap();
+            time.set(time.get() + frame);
+        }
+        time.set(50_000);
+        renderer.update_leds().unwrap();
+        let led = Color::from_bgra(renderer.controller().leds()[0]);
+        assert_eq!(led.red, 128);
+        results.push(led);
+        time.set(150_000);
+        renderer.update_leds().unwrap();
+        assert_eq!(Color::from_bgra(renderer.controller().leds()[0]).red, 255);
+    }
+    assert_eq!(results[0], results[1]);
+}
+
+#[test]
+fn easing_curves() {
+    let mut curves = vec![
+        Easing::CubicBezier(0, 0, 255, 255),
+        Easing::CubicBezier(107, 0, 255, 255),
+    ];
+    for id in 1..15 {
+        curves.push(Easing::deserialize(id, &[]).unwrap().0);
+    }
+    for easing in curves.iter() {
+        assert!(easing.apply(0.0).abs() < 1e-3, "{:?}", easing);
+        assert!((easing.apply(1.0) - 1.0).abs() < 1e-3, "{:?}", easing);
+        let mut buf = [0; Easing::MAX_PARAMS];
+        let len = easing.serialize_params(&mut buf);
+        assert_eq!(
+            Easing::deserialize(easing.id(), &buf[..len]).unwrap(),
+            (*easing, len)
+        );
+    }
+    // a bezier with control points on the diagonal is linear
+    let linear = Easing::CubicBezier(85, 85, 170, 170);
+    for i in 0..=10 {
+        let t = i as f32 / 10.0;
+        assert!((linear.apply(t) - t).abs() < 1e-2);
+    }
+    assert!(Easing::InQuad.apply(0.5) < 0.5);
+    assert!(Easing::OutQuad.apply(0.5) > 0.5);
+
+    let noise = Noise::new(0, 0, 0);
+    let pulse = Command::PulseLinear(10);
+    let half = effects::eval(pulse, Color::WHITE.into(), &noise, None, 50_000, 0, 1).unwrap();
+    assert_eq!(half.to_color().red, 128);
+    let eased = effects::eval(
+        pulse,
+        Color::WHITE.into(),
+        &noise,
+        Some(Easing::InQuad),
+        50_000,
+        0,
+        1,
+    );
+    assert_eq!(eased.unwrap().to_color().red, 191);
+}
+
+#[test]
+fn independent_stacks() {
+    let (mut renderer, _, msgs) = test_renderer(16);
+    renderer.color_map[1] = Color::RED;
+    renderer.color_map[2] = Color::BLUE;
+    let layout = &mut renderer.layout;
+    layout.add_segment(Segment::new("left", 0, 8, Direction::Forward));
+    layout.add_segment(Segment::new("right", 8, 8, Direction::Reverse));
+    layout.assign(0..2, "left").unwrap();
+    layout.assign(2..4, "right").unwrap();
+    assert!(layout.assign(4..5, "missing").is_err());
+    let bar = |element, color, v| LedMsg {
+        element,
+        color,
+        cmd: Command::FlatStack(v),
+        ..LedMsg::default()
+    };
+    // each bar is 2/8ths of its segment
+    msgs.borrow_mut().push(vec![
+        bar(0, 1, 63),
+        bar(1, 2, 63),
+        bar(2, 1, 63),
+        bar(3, 2, 63),
+    ]);
+    renderer.update_leds().unwrap();
+    let leds: Vec<Color> = renderer
+        .controller()
+        .leds()
+        .iter()
+        .map(|c| Color::from_bgra(*c))
+        .collect();
+    let (r, b, k) = (Color::RED, Color::BLUE, Color::BLACK);
+    assert_eq!(leds, [r, r, b, b, k, k, k, k, k, k, k, k, b, b, r, r]);
+}
+
+#[test]
+fn anti_aliased_bars() {
+    let (mut renderer, time, msgs) = test_renderer(8);
+    renderer.color_map[1] = Color::WHITE;
+    let bar = |element, v| LedMsg {
+        element,
+        color: 1,
+        cmd: Command::FlatStack(v),
+        ..LedMsg::default()
+    };
+    // 1.5 LEDs followed by 1 LED
+    msgs.borrow_mut().push(vec![bar(0, 47), bar(1, 31)]);
+    renderer.update_leds().unwrap();
+    let reds: Vec<u8> = renderer
+        .controller()
+        .leds()
+        .iter()
+        .map(|c| Color::from_bgra(*c).red)
+        .collect();
+    // the partial LEDs of neighboring bars add up to full brightness
+    assert_eq!(reds, [255, 255, 128, 0, 0, 0, 0, 0]);
+
+    let wipe = LedMsg {
+        element: 0,
+        color: 1,
+        cmd: Command::Wipe { period: 1000 },
+        ..LedMsg::default()
+    };
+    let off = LedMsg {
+        element: 1,
+        ..LedMsg::default()
+    };
+    msgs.borrow_mut().push(vec![wipe, off]);
+    time.set(312_500);
+    renderer.update_leds().unwrap();
+    let reds: Vec<u8> = renderer
+        .controller()
+        .leds()
+        .iter()
+        .map(|c| Color::from_bgra(*c).red)
+        .collect();
+    assert_eq!(reds, [255, 255, 128, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn segment_symmetry() {
+    let lit = |symmetry, positions: std::ops::Range<usize>| {
+        let seg = Segment::new("s", 2, 10, Direction::Forward).with_symmetry(symmetry);
+        let mut leds = [false; 14];
+        for pos in positions {
+            seg.for_each_led(pos, leds.len(), |led| leds[led] = true);
+        }
+        let on: Vec<usize> = (0..leds.len()).filter(|i| leds[*i]).collect();
+        (seg.positions(14), on)
+    };
+    assert_eq!(lit(Symmetry::None, 0..2), (10, vec![2, 3]));
+    assert_eq!(lit(Symmetry::CenterOut, 0..2), (5, vec![5, 6, 7, 8]));
+    assert_eq!(lit(Symmetry::EdgesIn, 0..2), (5, vec![2, 3, 10, 11]));
+    assert_eq!(lit(Symmetry::Mirror(2), 0..2), lit(Symmetry::EdgesIn, 0..2));
+    assert_eq!(lit(Symmetry::Repeat(2), 0..2), (5, vec![2, 3, 7, 8]));
+    assert_eq!(lit(Symmetry::Repeat(3), 3..4), (4, vec![5, 9]));
+    assert_eq!(lit(Symmetry::Mirror(3), 0..1), (4, vec![2, 9, 10]));
+    // on odd lengths, the mirrored half is shorter and meets the other in the center
+    let draws = |symmetry| {
+        let seg = Segment::new("s", 0, 9, Direction::Forward).with_symmetry(symmetry);
+        (0..seg.positions(9))
+            .map(|pos| {
+                let mut on = Vec::new();
+                seg.for_each_led(pos, 9, |led| on.push(led));
+                on.sort_unstable();
+                on
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(draws(Symmetry::Mirror(2)), draws(Symmetry::EdgesIn));
+    // odd lengths share the center LED
+    let seg = Segment::new("s", 0, 5, Direction::Forward).with_symmetry(Symmetry::CenterOut);
+    let mut center = Vec::new();
+    seg.for_each_led(0, 5, |led| center.push(led));
+    assert_eq!(center, [2]);
+}
+
+#[test]
+fn dithering_keeps_dim_colors() {
+    let (mut renderer, _, msgs) = test_renderer(1);
+    renderer.dither = true;
+    renderer.color_map[1] = Color {
+        red: 77,
+        green: 0,
+        blue: 0,
+        alpha: 255,
+    };
+    // 77 / 255 of the lowest step, which rounds to off without dithering
+    msgs.borrow_mut().push(vec![LedMsg {
+        color: 1,
+        cmd: Command::Flat(1),
+        ..LedMsg::default()
+    }]);
+    let mut total = 0;
+    for _ in 0..100 {
+        renderer.update_leds().unwrap();
+        total += Color::from_bgra(renderer.controller().leds()[0]).red as u32;
+    }
+    assert!((29..=31).contains(&total), "{}", total);
+}
+
+#[test]
+fn calibration() {
+    let cal: Calibration = "# comment\ngamma = 2.0\n\ngain = 1 0.5 0.25\n"
+        .parse()
+        .unwrap();
+    assert_eq!(cal.gamma, [2.0; 3]);
+    assert_eq!(cal.gain, [1.0, 0.5, 0.25]);
+    assert!("gamma = 1 2".parse::<Calibration>().is_err());
+    assert!("gamma 2".parse::<Calibration>().is_err());
+    assert!("gain = -1".parse::<Calibration>().is_err());
+    assert!("hue = 1".parse::<Calibration>().is_err());
+
+    let (mut renderer, _, msgs) = test_renderer(1);
+    renderer.calibration = cal;
+    renderer.color_map[1] = Color::WHITE * 0.5;
+    msgs.borrow_mut().push(vec![LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    }]);
+    renderer.update_leds().unwrap();
+    let led = Color::from_bgra(renderer.controller().leds()[0]);
+    // 128 / 255 squared is about a quarter
+    assert_eq!((led.red, led.green, led.blue), (64, 32, 16));
+}
+
+#[test]
+fn power_limit() {
+    let (mut renderer, _, msgs) = test_renderer(10);
+    renderer.color_map[1] = Color::WHITE;
+    let limit = PowerLimit {
+        channel_ma: [20.0; 3],
+        white_ma: 30.0,
+        idle_ma: 1.0,
+        budget_ma: 310.0,
+    };
+    renderer.power_limit = Some(limit);
+    msgs.borrow_mut().push(vec![LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    }]);
+    renderer.update_leds().unwrap();
+    let power = renderer.power();
+    assert!((power.requested_ma - 610.0).abs() < 1e-3);
+    assert!((power.output_ma - 310.0).abs() < 1e-3);
+    assert!((power.scale - 0.5).abs() < 1e-3);
+    let led = Color::from_bgra(renderer.controller().leds()[0]);
+    assert_eq!(led.red, 128);
+
+    // frames within the budget are left alone
+    renderer.power_limit = Some(PowerLimit {
+        budget_ma: 1000.0,
+        ..limit
+    });
+    renderer.update_leds().unwrap();
+    assert_eq!(renderer.power().scale, 1.0);
+    let led = Color::from_bgra(renderer.controller().leds()[0]);
+    assert_eq!(led.red, 255);
+
+    // on RGBW LEDs, white is drawn by the white channel
+    let ctl = WithPixelFormats::new(TestController(vec![[0; 4]; 10]), vec![PixelFormat::BGRW]);
+    let (mut renderer, _, msgs) = test_renderer_with(ctl.unwrap());
+    renderer.color_map[1] = Color::WHITE;
+    renderer.power_limit = Some(limit);
+    msgs.borrow_mut().push(vec![LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    }]);
+    renderer.update_leds().unwrap();
+    assert!((renderer.power().requested_ma - 310.0).abs() < 1e-3);
+}
+
+#[test]
+fn flash_limit() {
+    // counts the transitions of the first LED at 60 fps over two seconds
+    let count_transitions = |limit: Option<FlashLimiter>| {
+        let (mut renderer, time, msgs) = test_renderer(4);
+        renderer.color_map[1] = Color::WHITE;
+        renderer.flash_limit = limit;
+        // a 10 Hz strobe
+        msgs.borrow_mut().push(vec![LedMsg {
+            color: 1,
+            cmd: Command::Strobe {
+                period: 100,
+                duty: 128,
+            },
+            ..LedMsg::default()
+        }]);
+        // like the limiter, start from the first frame rather than from black
+        let mut level = None;
+        let mut transitions = Vec::new();
+        for frame in 0..120 {
+            time.set(frame * 1_000_000 / 60);
+            renderer.update_leds().unwrap();
+            let v = Color::from_bgra(renderer.controller().leds()[0]).red as f32 / 255.0;
+            let level = level.get_or_insert(v);
+            if (v - *level).abs() >= 0.1 {
+                *level = v;
+                transitions.push(time.get());
+            }
+        }
+        transitions
+    };
+    assert!(count_transitions(None).len() > 30);
+    let limited = count_transitions(Some(FlashLimiter::new(3.0, 0.1, 4)));
+    assert!(!limited.is_empty());
+    for (i, t) in limited.iter().enumerate() {
+        let in_window = limited[i..]
+            .iter()
+            .take_while(|t2| **t2 - t < 1_000_000)
+            .count();
+        assert!(in_window <= 6, "{:?}", limited);
+    }
+
+    // a single fast fade is one transition, so it passes through unchanged
+    let mut limiter = FlashLimiter::new(3.0, 0.1, 4);
+    for frame in 0..60 {
+        let v = (frame as f32 / 30.0).min(1.0);
+        let mut leds = [ColorF::from(Color::WHITE) * v; 4];
+        limiter.apply(frame * 1_000_000 / 60, &mut leds);
+        assert_eq!(leds[0], ColorF::from(Color::WHITE) * v, "frame {}", frame);
+    }
+    // a bright first frame isn't a transition
+    let mut limiter = FlashLimiter::new(0.5, 0.1, 4);
+    let mut leds = [ColorF::from(Color::WHITE); 4];
+    limiter.apply(0, &mut leds);
+    let mut leds = [ColorF::BLACK; 4];
+    limiter.apply(16_000, &mut leds);
+    assert_eq!(leds[0], ColorF::BLACK);
+}
+
+#[test]
+fn global_commands() {
+    let (mut renderer, time, msgs) = test_renderer(2);
+    renderer.color_map[1] = Color::WHITE;
+    let msg = |time, cmd| LedMsg {
+        time,
+        color: 1,
+        cmd,
+        ..LedMsg::default()
+    };
+    let red = |renderer: &TestRenderer| Color::from_bgra(renderer.controller().leds()[0]).red;
+    msgs.borrow_mut().push(vec![msg(0, Command::Flat(255))]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 255);
+
+    // the element and color of global messages don't matter
+    msgs.borrow_mut().push(vec![LedMsg {
+        element: 9,
+        color: 3,
+        ..msg(0, Command::Dimmer(128))
+    }]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 128);
+
+    msgs.borrow_mut().push(vec![msg(0, Command::Blackout)]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 0);
+    // new messages don't light a blacked out receiver
+    msgs.borrow_mut().push(vec![msg(0, Command::Flat(64))]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 0);
+
+    // resuming shows the latest message, and global messages wait for their time
+    time.set(1_000);
+    msgs.borrow_mut().push(vec![
+        msg(1_000, Command::Resume),
+        msg(2_000, Command::Freeze),
+    ]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 32);
+    time.set(2_000);
+    renderer.update_leds().unwrap();
+    assert!(renderer.frozen);
+    msgs.borrow_mut().push(vec![msg(2_000, Command::Flat(255))]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 32);
+    // the dimmer still applies to a frozen frame
+    msgs.borrow_mut()
+        .push(vec![msg(2_000, Command::Dimmer(255))]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 64);
+    msgs.borrow_mut().push(vec![msg(2_000, Command::Resume)]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 255);
+    // stale global messages expire like any other
+    time.set(8_000_000);
+    msgs.borrow_mut().push(vec![
+        msg(8_000_000, Command::Flat(255)),
+        msg(2_000, Command::Blackout),
+    ]);
+    renderer.update_leds().unwrap();
+    assert_eq!(red(&renderer), 255);
+}
+
+#[test]
+fn merge_policies() {
+    let (mut renderer, time, msgs) = test_renderer(1);
+    renderer.color_map[1] = Color::WHITE;
+    let msg = |source, v| LedMsg {
+        color: 1,
+        cmd: Command::Flat(v),
+        source,
+        ..LedMsg::default()
+    };
+    let show = |renderer: &mut TestRenderer, batch: Vec<LedMsg>| {
+        msgs.borrow_mut().push(batch);
+        renderer.update_leds().unwrap();
+        Color::from_bgra(renderer.controller().leds()[0]).red
+    };
+    // the last message received is shown by default
+    assert_eq!(show(&mut renderer, vec![msg(1, 200)]), 200);
+    assert_eq!(show(&mut renderer, vec![msg(2, 100)]), 100);
+
+    renderer.set_merge_policy(0, MergePolicy::Highest);
+    assert_eq!(show(&mut renderer, vec![msg(1, 200)]), 200);
+    assert_eq!(show(&mut renderer, vec![msg(2, 150)]), 200);
+    // lowering the brightest source hands the element to the other one
+    assert_eq!(show(&mut renderer, vec![msg(1, 50)]), 150);
+    // stacked elements are as bright as the share of the segment they fill
+    let stack = |v| LedMsg {
+        cmd: Command::FlatStack(v),
+        ..msg(1, 0)
+    };
+    assert_eq!(show(&mut renderer, vec![stack(127)]), 150);
+    assert_eq!(show(&mut renderer, vec![stack(255)]), 255);
+    // pulses are compared by their current brightness
+    let pulse = LedMsg {
+        cmd: Command::PulseLinear(100),
+        ..msg(1, 0)
+    };
+    assert_eq!(show(&mut renderer, vec![pulse]), 255);
+    time.set(800_000);
+    assert_eq!(show(&mut renderer, vec![]), 150);
+
+    renderer.set_merge_policy(0, MergePolicy::Priority);
+    renderer.source_priority[2] = 1;
+    assert_eq!(show(&mut renderer, vec![msg(1, 250)]), 150);
+    assert_eq!(show(&mut renderer, vec![msg(2, 10)]), 10);
+    renderer.source_priority[2] = 0;
+    assert_eq!(show(&mut renderer, vec![msg(1, 250)]), 250);
+
+    renderer.set_merge_policy(0, MergePolicy::Exclusive);
+    // the earlier messages expire, leaving the element free
+    time.set(6_000_000);
+    let at = |time, msg: LedMsg| LedMsg { time, ..msg };
+    assert_eq!(show(&mut renderer, vec![at(6_000_000, msg(3, 30))]), 30);
+    assert_eq!(renderer.lock_holder(0), Some(3));
+    assert_eq!(show(&mut renderer, vec![at(6_000_000, msg(1, 60))]), 30);
+    assert_eq!(show(&mut renderer, vec![at(6_000_000, msg(3, 90))]), 90);
+    // the lock is released once the holder's messages expire
+    time.set(11_500_000);
+    assert_eq!(show(&mut renderer, vec![at(11_500_000, msg(1, 60))]), 60);
+    assert_eq!(renderer.lock_holder(0), Some(1));
+    // the oldest message claims a free element, even when received in the same batch
+    time.set(17_000_000);
+    let batch = vec![at(17_000_000, msg(4, 40)), at(17_000_000, msg(5, 50))];
+    assert_eq!(show(&mut renderer, batch), 40);
+    assert_eq!(renderer.lock_holder(0), Some(4));
+}
+
+#[test]
+fn idle_behavior() {
+    let run = |idle: Idle| {
+        let (mut renderer, time, msgs) = test_renderer(2);
+        renderer.color_map[1] = Color::WHITE;
+        renderer.color_map[2] = Color::BLUE;
+        renderer.idle = idle;
+        renderer.idle_timeout = Duration::from_secs(1);
+        renderer.idle_fade = Duration::from_millis(100);
+        let flat = |time| LedMsg {
+            time,
+            color: 1,
+            cmd: Command::Flat(255),
+            ..LedMsg::default()
+        };
+        msgs.borrow_mut().push(vec![flat(0)]);
+        let (mut frames, mut idles) = (Vec::new(), Vec::new());
+        // a packet arrives at 1.55 s, after the receiver went idle at 1 s
+        for t in [0, 1_050, 1_500, 1_550, 1_600, 1_750].iter() {
+            time.set(t * 1000);
+            if *t == 1_550 {
+                msgs.borrow_mut().push(vec![flat(t * 1000)]);
+            }
+            renderer.update_leds().unwrap();
+            frames.push(Color::from_bgra(renderer.controller().leds()[0]));
+            idles.push(renderer.is_idle());
+        }
+        if idle != Idle::Dark {
+            assert_eq!(idles, [false, true, true, true, true, false]);
+        }
+        frames
+    };
+    let white = Color::WHITE;
+    // without an idle behavior the message simply expires
+    assert!(run(Idle::Dark).iter().all(|c| *c == white));
+    let frames = run(Idle::FadeOut);
+    assert_eq!(frames[0], white);
+    assert_eq!(frames[1].red, 128);
+    assert_eq!(frames[2], Color::BLACK);
+    // fading back to live control
+    assert_eq!(frames[3], Color::BLACK);
+    assert_eq!(frames[4].red, 128);
+    assert_eq!(frames[5], white);
+    assert!(run(Idle::Hold).iter().all(|c| *c == white));
+    let blue = LedMsg {
+        color: 2,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    };
+    let frames = run(Idle::Animate(blue));
+    assert_eq!((frames[1].red, frames[1].blue), (128, 255));
+    assert_eq!(frames[2], Color::BLUE);
+    assert_eq!(frames[5], white);
+}
+
+#[test]
+fn presentation_delay() {
+    let (mut renderer, time, msgs) = test_renderer(1);
+    renderer.color_map[1] = Color::WHITE;
+    renderer.set_presentation_delay(Duration::from_millis(50));
+    assert_eq!(renderer.presentation_delay(), Duration::from_millis(50));
+    // a pulse fading out over 100ms
+    msgs.borrow_mut().push(vec![LedMsg {
+        time: 100_000,
+        color: 1,
+        cmd: Command::PulseLinear(10),
+        ..LedMsg::default()
+    }]);
+    time.set(150_000);
+    renderer.update_leds().unwrap();
+    assert_eq!(Color::from_bgra(renderer.controller().leds()[0]).red, 255);
+    time.set(200_000);
+    renderer.update_leds().unwrap();
+    assert_eq!(Color::from_bgra(renderer.controller().leds()[0]).red, 128);
+
+    // senders can query the delay from the receiver
+    let (sender, recv) = channel(1);
+    let mut renderer = Renderer::new(recv, TestController(vec![[0; 4]; 1]));
+    assert_eq!(sender.presentation_delay().unwrap(), Duration::from_secs(0));
+    renderer.set_presentation_delay(Duration::from_millis(120));
+    assert_eq!(
+        sender.presentation_delay().unwrap(),
+        Duration::from_millis(120)
+    );
+}
+
+/// A `Receiver` that fails after a number of frames, to stop the frame loop.
+struct FrameLimit {
+    start: Instant,
+    frames: u32,
+}
+impl Receiver for FrameLimit {
+    fn cur_time(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+    fn recv_to(&mut self, _timeout: Duration) -> Result<Vec<LedMsg>, Error> {
+        if self.frames == 0 {
+            return Err(Error::Unrecoverable("FrameLimit: done".to_string()));
+        }
+        self.frames -= 1;
+        Err(Error::Timeout("FrameLimit: no msgs".to_string()))
+    }
+    fn recv(&mut self) -> Result<Vec<LedMsg>, Error> {
+        self.recv_to(Duration::from_secs(0))
+    }
+}
+
+#[test]
+fn frame_stats() {
+    let recv = FrameLimit {
+        start: Instant::now(),
+        frames: 20,
+    };
+    let mut renderer = Renderer::new(recv, TestController(vec![[0; 4]; 4]));
+    renderer.stats_period = Duration::from_millis(50);
+    let mut periods = Vec::new();
+    let start = Instant::now();
+    let err = renderer.update_leds_loop_with(200.0, |stats| periods.push(stats.clone()));
+    assert!(matches!(err, Error::Unrecoverable(_)));
+    // 20 frames at 200 fps takes 95ms, as the first frame is immediate
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(95), "{:?}", elapsed);
+    let stats = periods.last().unwrap();
+    assert_eq!(renderer.stats(), stats);
+    assert!(stats.frames > 0);
+    assert!(stats.frame_time_p50 >= Duration::from_millis(4));
+    assert!(stats.frame_time_p50 <= stats.frame_time_p95);
+    assert!(stats.frame_time_p95 <= stats.frame_time_max);
+
+    let mut collector = StatsCollector::new(start, 3, 1);
+    for ms in 1..=100 {
+        collector.record(Some(Duration::from_millis(ms)), Duration::from_millis(1));
+    }
+    collector.missed_deadlines = 2;
+    let stats = collector.finish(start + Duration::from_secs(1), 10, 2);
+    assert_eq!(stats.frames, 100);
+    assert!((stats.fps - 100.0).abs() < 1e-6);
+    assert_eq!(stats.frame_time_p50, Duration::from_millis(51));
+    assert_eq!(stats.frame_time_p95, Duration::from_millis(95));
+    assert_eq!(stats.frame_time_max, Duration::from_millis(100));
+    assert_eq!(stats.render_time_mean, Duration::from_millis(1));
+    assert_eq!(stats.missed_deadlines, 2);
+    assert_eq!((stats.msgs_applied, stats.msgs_expired), (7, 1));
+}
+
+#[test]
+fn spawned_renderer() {
+    let (mut sender, recv) = channel(8);
+    let mut renderer = Renderer::new(recv, TestController(vec![[0; 4]; 2]));
+    renderer.stats_period = Duration::from_millis(20);
+    renderer.dither = false;
+    let handle = renderer.spawn(200.0).unwrap();
+    let mut color_map = ColorMap::default();
+    color_map[1] = Color::GREEN;
+    handle.set_color_map(color_map).unwrap();
+    handle.set_dimmer(0.5).unwrap();
+    let flat = LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    };
+    sender.send(&mut [flat], true).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(handle.stats().frames > 0);
+    // the receiver is drained while paused, so senders don't stall on a full channel
+    handle.pause().unwrap();
+    for _ in 0..20 {
+        sender.send(&mut [flat], true).unwrap();
+    }
+    handle.resume().unwrap();
+    assert!(!handle.is_finished());
+    let renderer = handle.stop().unwrap();
+    let led = Color::from_bgra(renderer.controller().leds()[0]);
+    assert_eq!((led.red, led.green), (0, 128));
+
+    // an error from the receiver stops the renderer
+    let handle = renderer.spawn(200.0).unwrap();
+    drop(sender);
+    assert!(matches!(handle.wait(), Error::Unrecoverable(_)));
+
+    // renderers created on their thread don't have to be `Send`
+    let handle = TestRenderer::spawn_with(200.0, || Ok(test_renderer(2).0)).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!handle.is_finished());
+    handle.stop().unwrap();
+    // an error creating the renderer is returned by the handle
+    let handle =
+        TestRenderer::spawn_with(200.0, || Err(Error::Misc("no controller".to_string()))).unwrap();
+    assert!(matches!(handle.wait(), Error::Misc(_)));
+}
+
+#[test]
+fn async_renderer() {
+    let (mut sender, recv) = async_channel(8);
+    let mut renderer = Renderer::new_async(recv, TestController(vec![[0; 4]; 2]));
+    renderer.color_map[1] = Color::BLUE;
+    let send = async move {
+        for v in [64, 255].iter() {
+            let mut msgs = [LedMsg {
+                color: 1,
+                cmd: Command::Flat(*v),
+                ..LedMsg::default()
+            }];
+            AsyncSender::send(&mut sender, &mut msgs, true)
+                .await
+                .unwrap();
+            crate::rt::sleep(Duration::from_millis(20)).await;
+        }
+        // dropping the sender ends the loop
+    };
+    let (err, _) = crate::rt::block_on(futures::future::join(
+        renderer.update_leds_async_loop(200.0),
+        send,
+    ));
+    assert!(matches!(err, Error::Unrecoverable(_)));
+    let led = Color::from_bgra(renderer.controller().leds()[0]);
+    assert_eq!(led.blue, 255);
+
+    // async receivers also work with the blocking frame loops
+    let (mut sender, recv) = async_channel(8);
+    let mut renderer = Renderer::new_async(recv, TestController(vec![[0; 4]; 2]));
+    renderer.color_map[1] = Color::RED;
+    renderer.dither = false;
+    let mut msgs = [LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    }];
+    let send = std::thread::spawn(move || {
+        crate::rt::block_on(AsyncSender::send(&mut sender, &mut msgs, true)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+    });
+    assert!(matches!(
+        renderer.update_leds_loop(200.0),
+        Error::Unrecoverable(_)
+    ));
+    send.join().unwrap();
+    assert_eq!(Color::from_bgra(renderer.controller().leds()[0]).red, 255);
+
+    // receivers can also be used as streams
+    let (mut sender, mut recv) = async_channel(8);
+    let batches = crate::rt::block_on(async {
+        for i in 0..3 {
+            let mut msgs = [LedMsg {
+                element: i,
+                ..LedMsg::default()
+            }];
+            AsyncSender::send(&mut sender, &mut msgs, false)
+                .await
+                .unwrap();
+        }
+        drop(sender);
+        recv.stream().collect::<Vec<_>>().await
+    });
+    assert_eq!(batches.len(), 4);
+    assert_eq!(batches[2].as_ref().unwrap()[0].element, 2);
+    assert!(batches[3].is_err());
+}
+
+#[test]
+fn merged_receiver() {
+    let (mut backup, backup_recv) = channel(8);
+    std::thread::sleep(Duration::from_millis(20));
+    let (mut primary, primary_recv) = channel(8);
+    let mut merged = MergedReceiver::new();
+    merged.failover = Duration::from_millis(50);
+    let backup_idx = merged.add(backup_recv, 0);
+    let primary_idx = merged.add(primary_recv, 1);
+    let send = |sender: &mut crate::LocalSender, element: u8| {
+        let mut msgs = [LedMsg {
+            element,
+            time: 0,
+            ..LedMsg::default()
+        }];
+        sender.send(&mut msgs, true).unwrap();
+    };
+
+    // messages are converted into the merged time base
+    send(&mut backup, 0);
+    let msgs = merged.recv_to(Duration::from_millis(10)).unwrap();
+    let diff = merged.cur_time().wrapping_sub(msgs[0].time) as i64;
+    assert!((0..5_000).contains(&diff), "{}", diff);
+    assert_eq!(merged.active(), Some(backup_idx));
+
+    // the primary outranks the backup while it is sending
+    send(&mut primary, 1);
+    let msgs = merged.recv_to(Duration::from_millis(10)).unwrap();
+    assert_eq!(msgs[0].element, 1);
+    let diff = merged.cur_time().wrapping_sub(msgs[0].time) as i64;
+    assert!((0..5_000).contains(&diff), "{}", diff);
+    send(&mut backup, 0);
+    assert!(matches!(
+        merged.recv_to(Duration::from_millis(10)),
+        Err(Error::Timeout(_))
+    ));
+    assert_eq!(merged.active(), Some(primary_idx));
+
+    // the backup takes over once the primary goes quiet
+    std::thread::sleep(Duration::from_millis(60));
+    send(&mut backup, 0);
+    assert_eq!(
+        merged.recv_to(Duration::from_millis(10)).unwrap()[0].element,
+        0
+    );
+    assert_eq!(merged.active(), Some(backup_idx));
+
+    // failed sources are skipped until none are left
+    drop(primary);
+    send(&mut backup, 0);
+    assert_eq!(merged.recv_to(Duration::from_millis(10)).unwrap().len(), 1);
+    drop(backup);
+    assert!(matches!(
+        merged.recv_to(Duration::from_millis(10)),
+        Err(Error::Unrecoverable(_))
+    ));
+}
+
+#[test]
+fn merged_receiver_noise() {
+    let (mut backup, backup_recv) = channel(8);
+    std::thread::sleep(Duration::from_millis(20));
+    let (mut primary, primary_recv) = channel(8);
+    let mut merged = MergedReceiver::new();
+    merged.add(backup_recv, 0);
+    merged.add(primary_recv, 1);
+    let sparkle = |time: u64| LedMsg {
+        element: 2,
+        time,
+        cmd: Command::Sparkle {
+            period: 100,
+            density: 128,
+            seed: 3,
+        },
+        ..LedMsg::default()
+    };
+
+    // the merged receiver uses the clock of the primary,
+    // so its messages are seeded the same as for a fixture listening to it directly
+    let sender_time = primary.get_time();
+    let diff = merged.cur_time().wrapping_sub(sender_time) as i64;
+    assert!((0..5_000).contains(&diff), "{}", diff);
+    let mut msgs = [sparkle(primary.get_time() + 1_000)];
+    let bare = Noise::from_msg(&msgs[0]);
+    primary.send(&mut msgs, false).unwrap();
+    let msgs = merged.recv_to(Duration::from_millis(10)).unwrap();
+    assert_eq!(Noise::from_msg(&msgs[0]), bare);
+
+    // once the primary fails, the backup's clock takes over
+    drop(primary);
+    assert!(matches!(
+        merged.recv_to(Duration::from_millis(10)),
+        Err(Error::Timeout(_))
+    ));
+    let mut msgs = [sparkle(backup.get_time() + 1_000)];
+    let bare = Noise::from_msg(&msgs[0]);
+    backup.send(&mut msgs, false).unwrap();
+    let msgs = merged.recv_to(Duration::from_millis(10)).unwrap();
+    assert_eq!(Noise::from_msg(&msgs[0]), bare);
+}
+
+#[test]
+fn multi_sender() {
+    let (first, mut first_recv) = async_channel(8);
+    std::thread::sleep(Duration::from_millis(20));
+    let (second, mut second_recv) = async_channel(8);
+    let (third, third_recv) = async_channel(8);
+    let mut sender = MultiSender::new();
+    sender.add(first);
+    sender.add(second);
+    sender.add(third);
+    drop(third_recv);
+
+    // times are converted into the clock of each link
+    let mut msgs = [LedMsg {
+        time: Sender::get_time(&sender),
+        ..LedMsg::default()
+    }];
+    let results = crate::rt::block_on(sender.send_all(&msgs, false));
+    assert!(results[0].is_ok() && results[1].is_ok());
+    assert!(matches!(results[2], Err(Error::Unrecoverable(_))));
+    for recv in [&mut first_recv, &mut second_recv].iter_mut() {
+        let msgs = crate::rt::block_on(AsyncReceiver::recv(&mut **recv)).unwrap();
+        let diff = AsyncReceiver::cur_time(&**recv).wrapping_sub(msgs[0].time) as i64;
+        assert!((0..5_000).contains(&diff), "{}", diff);
+    }
+
+    // a failed link doesn't stop the others
+    let err = Sender::send(&mut sender, &mut msgs, true).unwrap_err();
+    assert!(matches!(err, Error::Misc(_)));
+    assert_eq!(
+        crate::rt::block_on(AsyncReceiver::recv(&mut second_recv))
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+struct MultiOutputController(Vec<Vec<[u8; 4]>>);
+impl Controller for MultiOutputController {
+    fn output_bytes(&self, output: usize) -> &[u8] {
+        self.0[output].as_flattened()
+    }
+    fn output_bytes_mut(&mut self, output: usize) -> &mut [u8] {
+        self.0[output].as_flattened_mut()
+    }
+    fn render(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn outputs(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[test]
+fn multiple_outputs() {
+    let ctl = MultiOutputController(vec![vec![[0; 4]; 3], vec![[0; 4]; 2]]);
+    let (mut renderer, _, msgs) = test_renderer_with(ctl);
+    renderer.color_map[1] = Color::WHITE;
+    assert_eq!(renderer.controller().led_count(), 5);
+    assert_eq!(renderer.output_range(1), 3..5);
+    // one segment spans the end of the first output and the start of the second
+    renderer
+        .layout
+        .add_segment(Segment::new("span", 2, 2, Direction::Forward));
+    renderer.layout.assign(0..1, "span").unwrap();
+    msgs.borrow_mut().push(vec![LedMsg {
+        color: 1,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    }]);
+    renderer.update_leds().unwrap();
+    let lit = |output: usize| -> Vec<bool> {
+        renderer
+            .controller()
+            .output_leds(output)
+            .iter()
+            .map(|led| Color::from_bgra(*led).red == 255)
+            .collect()
+    };
+    assert_eq!(lit(0), [false, false, true]);
+    assert_eq!(lit(1), [true, false]);
+}
+
+/// A controller with one output of 16-bit RGB LEDs.
+struct WideController(Vec<u8>);
+impl Controller for WideController {
+    fn render(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn pixel_format(&self, _output: usize) -> PixelFormat {
+        PixelFormat::RGB.with_depth(BitDepth::Sixteen)
+    }
+    fn output_bytes(&self, _output: usize) -> &[u8] {
+        &self.0
+    }
+    fn output_bytes_mut(&mut self, _output: usize) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+#[test]
+fn pixel_formats() {
+    let color = Color {
+        red: 1,
+        green: 2,
+        blue: 3,
+        alpha: 4,
+    };
+    assert_eq!(color.to_rgb(), [1, 2, 3]);
+    assert_eq!(color.to_rgba(), [1, 2, 3, 4]);
+    assert_eq!("grbw".parse::<PixelFormat>().unwrap(), PixelFormat::GRBW);
+    let wide: PixelFormat = "RGBX16".parse().unwrap();
+    assert_eq!(wide.bytes_per_led(), 8);
+    assert!("rgbr".parse::<PixelFormat>().is_err());
+    // overriding formats must match the controller's outputs
+    let ctl = || TestController(vec![[0; 4]; 2]);
+    assert!(WithPixelFormats::new(ctl(), vec![PixelFormat::RGB]).is_err());
+    assert!(WithPixelFormats::new(ctl(), vec![PixelFormat::GRBW; 2]).is_err());
+
+    let flat = |color| LedMsg {
+        color,
+        cmd: Command::Flat(255),
+        ..LedMsg::default()
+    };
+    let orange = Color {
+        red: 255,
+        green: 128,
+        blue: 64,
+        alpha: 0,
+    };
+    // the white shared by the color channels is moved to the white channel
+    let ctl =
+        WithPixelFormats::new(TestController(vec![[0; 4]; 1]), vec![PixelFormat::BGRW]).unwrap();
+    let (mut renderer, _, msgs) = test_renderer_with(ctl);
+    msgs.borrow_mut().push(vec![flat(1)]);
+    renderer.color_map[1] = orange;
+    renderer.update_leds().unwrap();
+    assert_eq!(renderer.controller().leds()[0], [0, 64, 191, 64]);
+
+    // 16-bit channels are scaled to their full range
+    let (mut renderer, _, msgs) = test_renderer_with(WideController(vec![0; 12]));
+    msgs.borrow_mut().push(vec![flat(1)]);
+    renderer.color_map[1] = orange;
+    assert_eq!(renderer.controller().led_count(), 2);
+    renderer.update_leds().unwrap();
+    let wide = PixelFormat::RGB.with_depth(BitDepth::Sixteen);
+    assert_eq!(
+        wide.read(&renderer.controller().0[6..]),
+        [65535, 32896, 16448, 0]
+    );
+}
+
+/// A controller that fails to render until `failures` reaches zero.
+struct FlakyController {
+    leds: Vec<[u8; 4]>,
+    failures: u32,
+    renders: u32,
+}
+impl Controller for FlakyController {
+    fn output_bytes(&self, _output: usize) -> &[u8] {
+        self.leds.as_flattened()
+    }
+    fn output_bytes_mut(&mut self, _output: usize) -> &mut [u8] {
+        self.leds.as_flattened_mut()
+    }
+    fn render(&mut self) -> Result<(), Error> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(Error::Controller("FlakyController: failed".to_string()));
+        }
+        self.renders += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn render_retry() {
+    let flaky = |failures| FlakyController {
+        leds: vec![[0; 4]; 2],
+        failures,
+        renders: 0,
+    };
+    let (mut renderer, _, _) = test_renderer_with(flaky(2));
+    for i in 1..=2 {
+        let err = renderer.update_leds().unwrap_err();
+        assert!(matches!(err, Error::Controller(_)));
+        assert_eq!(renderer.render_failures(), i);
+    }
+    // the frame is rendered again even though the LEDs haven't changed
+    renderer.update_leds().unwrap();
+    assert_eq!(renderer.render_failures(), 0);
+    assert_eq!(renderer.controller().renders, 1);
+    renderer.update_leds().unwrap();
+    assert_eq!(renderer.controller().renders, 1);
+
+    // the loop retries with backoff until it gives up
+    let recv = FrameLimit {
+        start: Instant::now(),
+        frames: 1000,
+    };
+    let mut renderer = Renderer::new(recv, flaky(u32::MAX));
+    renderer.render_retry = RenderRetry {
+        max_failures: Some(4),
+        backoff: Duration::from_millis(5),
+        max_backoff: Duration::from_millis(10),
+    };
+    let start = Instant::now();
+    let err = renderer.update_leds_loop(200.0);
+    assert!(matches!(err, Error::Controller(_)));
+    assert_eq!(renderer.render_failures(), 4);
+    // 5ms, 10ms and then 10ms again
+    assert!(start.elapsed() >= Duration::from_millis(25));
+
+    // a transient failure doesn't stop the loop
+    let recv = FrameLimit {
+        start: Instant::now(),
+        frames: 10,
+    };
+    let mut renderer = Renderer::new(recv, flaky(3));
+    renderer.render_retry.backoff = Duration::from_millis(1);
+    let err = renderer.update_leds_loop(200.0);
+    assert!(matches!(err, Error::Unrecoverable(_)));
+    assert_eq!(renderer.controller().renders, 1);
+}